@@ -0,0 +1,327 @@
+use std::path::{Path, PathBuf};
+
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, Documentation, Hover, HoverContents,
+    MarkupContent, MarkupKind, Position, Range,
+};
+use parser::{Instruction, Source, Spanned};
+
+// Every parse error in the script
+pub(crate) fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let Err(errors) = parser::parse(source) else { return vec![] };
+    errors
+        .iter()
+        .map(|error| {
+            let (start, end) = error.span();
+            Diagnostic {
+                range: range(source, start.offset as usize, end.offset as usize),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("parrot".into()),
+                message: error.message(),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+// Instruction keywords at the start of a line, or marker names after `goto`.
+// Markers are looked up in the files loaded by the script, relative to `dir`.
+pub(crate) fn completion(source: &str, position: Position, dir: Option<&Path>) -> Vec<CompletionItem> {
+    let offset = offset(source, position);
+    let line_start = line_start(source, offset);
+    let prefix = source[line_start..offset].trim_start();
+
+    if let Some(partial) = prefix.strip_prefix("goto ") {
+        if !partial.trim_start().chars().all(is_word) {
+            return vec![];
+        }
+        return markers(source, line_start, dir)
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name,
+                kind: Some(CompletionItemKind::REFERENCE),
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    if !prefix.chars().all(is_word) {
+        return vec![];
+    }
+
+    parser::keywords()
+        .map(|keyword| CompletionItem {
+            label: keyword.name.into(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(keyword.syntax.into()),
+            documentation: Some(Documentation::String(keyword.description.into())),
+            ..Default::default()
+        })
+        .collect()
+}
+
+// The syntax and description of the instruction keyword under the cursor
+pub(crate) fn hover(source: &str, position: Position) -> Option<Hover> {
+    let (start, word) = word_at(source, offset(source, position))?;
+
+    // Only the first word on a line starts an instruction
+    let before = &source[line_start(source, start)..start];
+    if !before.chars().all(|c| c.is_whitespace() || c == '{') {
+        return None;
+    }
+
+    let keyword = parser::keyword(word)?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```\n{}\n```\n{}", keyword.syntax, keyword.description),
+        }),
+        range: Some(range(source, start, start + word.len())),
+    })
+}
+
+// The `load` or `let` that binds the name under the cursor,
+// if the cursor is on a name that is typed, inserted or used as a replacement
+pub(crate) fn definition(source: &str, position: Position) -> Option<Range> {
+    let offset = offset(source, position);
+    let (_, word) = word_at(source, offset)?;
+    let instructions = parser::parse(source).ok()?.into_iter().collect::<Vec<_>>();
+    let instructions = flatten(&instructions);
+
+    let used = instructions.iter().any(|spanned| {
+        let span = spanned.start.offset as usize..=spanned.end.offset as usize;
+        span.contains(&offset) && uses(&spanned.instruction) == Some(word)
+    });
+    if !used {
+        return None;
+    }
+
+    // The closest binding before the cursor, or any if it's bound later on
+    let bindings = instructions
+        .iter()
+        .filter(|spanned| binds(&spanned.instruction) == Some(word))
+        .collect::<Vec<_>>();
+    let binding = bindings
+        .iter()
+        .rfind(|spanned| (spanned.start.offset as usize) < offset)
+        .or(bindings.first())?;
+
+    Some(range(source, binding.start.offset as usize, binding.end.offset as usize))
+}
+
+// Every instruction, including the ones in the bodies of `define` and `repeat`
+fn flatten(instructions: &[Spanned]) -> Vec<&Spanned> {
+    let mut output = vec![];
+    for spanned in instructions {
+        output.push(spanned);
+        if let Instruction::Define { body, .. } | Instruction::Repeat { body, .. } = &spanned.instruction {
+            output.extend(flatten(body));
+        }
+    }
+    output
+}
+
+fn uses(instruction: &Instruction) -> Option<&str> {
+    match instruction {
+        Instruction::Type {
+            source: Source::Ident(name),
+            ..
+        }
+        | Instruction::Insert(Source::Ident(name))
+        | Instruction::Replace {
+            replacement: Source::Ident(name),
+            ..
+        } => Some(name),
+        _ => None,
+    }
+}
+
+fn binds(instruction: &Instruction) -> Option<&str> {
+    match instruction {
+        Instruction::Load(_, name) | Instruction::Let { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+// The names of the markers in every file loaded by the script and every string it types.
+// The line at `line_start` is left out, as it is still being written and might not parse.
+fn markers(source: &str, line_start: usize, dir: Option<&Path>) -> Vec<String> {
+    let line_end = source[line_start..].find('\n').map_or(source.len(), |end| line_start + end);
+    let source = format!("{}{}", &source[..line_start], &source[line_end..]);
+    let Ok(instructions) = parser::parse(&source) else { return vec![] };
+    let instructions = instructions.into_iter().collect::<Vec<_>>();
+
+    let mut names = vec![];
+    for spanned in flatten(&instructions) {
+        let text = match &spanned.instruction {
+            Instruction::Load(path, _) => match std::fs::read_to_string(resolve(path, dir)) {
+                Ok(text) => text,
+                Err(_) => continue,
+            },
+            Instruction::Type {
+                source: Source::Str(text),
+                ..
+            }
+            | Instruction::Insert(Source::Str(text)) => text.clone(),
+            _ => continue,
+        };
+
+        let (_, markers) = ui::generate_markers(text);
+        for marker in markers.into_iter().flatten() {
+            if !names.iter().any(|name| name == marker.name()) {
+                names.push(marker.name().to_string());
+            }
+        }
+    }
+    names
+}
+
+// Relative paths are looked up next to the script first
+fn resolve(path: &Path, dir: Option<&Path>) -> PathBuf {
+    match dir {
+        Some(dir) if path.is_relative() && dir.join(path).is_file() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+// The word that the byte offset is in or right after, and where it starts
+fn word_at(source: &str, offset: usize) -> Option<(usize, &str)> {
+    let start = source[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(offset, |(index, _)| index);
+    let end = source[offset..].find(|c| !is_word(c)).map_or(source.len(), |end| offset + end);
+    let word = &source[start..end];
+    (!word.is_empty()).then_some((start, word))
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |index| index + 1)
+}
+
+// The range between two byte offsets, without any trailing whitespace.
+// An empty range is widened to the character after it.
+fn range(source: &str, start: usize, end: usize) -> Range {
+    let end = end.max(start);
+    let mut end = start + source[start..end].trim_end().len();
+    if end == start {
+        end += source[start..].chars().next().map_or(0, char::len_utf8);
+    }
+    Range::new(position(source, start), position(source, end))
+}
+
+// Editors count columns in UTF-16 code units
+fn position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let character = before[line_start(source, offset)..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+fn offset(source: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        if index as u32 == position.line {
+            let mut character = 0;
+            for (column, c) in line.char_indices() {
+                if character >= position.character || c == '\n' {
+                    return offset + column;
+                }
+                character += c.len_utf16() as u32;
+            }
+            return offset + line.len();
+        }
+        offset += line.len();
+    }
+    source.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn labels(items: Vec<CompletionItem>) -> Vec<String> {
+        items.into_iter().map(|item| item.label).collect()
+    }
+
+    #[test]
+    fn positions() {
+        let source = "type \"🦜\"\nwait 1s";
+        assert_eq!(position(source, 10), Position::new(0, 8));
+        assert_eq!(position(source, 17), Position::new(1, 5));
+        assert_eq!(offset(source, Position::new(0, 8)), 10);
+        assert_eq!(offset(source, Position::new(1, 5)), 17);
+        assert_eq!(offset(source, Position::new(0, 99)), 11);
+        assert_eq!(offset(source, Position::new(9, 0)), source.len());
+    }
+
+    #[test]
+    fn diagnostic_ranges() {
+        let diagnostics = diagnostics("clear\ntyp \"a\"\nwait nope");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(1, 0), Position::new(1, 3)));
+        assert!(diagnostics[0].message.contains("did you mean `type`?"));
+        assert_eq!(diagnostics[1].range.start, Position::new(2, 5));
+
+        assert!(super::diagnostics("clear").is_empty());
+    }
+
+    #[test]
+    fn complete_keywords() {
+        let source = "clear\n    ty";
+        let items = labels(completion(source, Position::new(1, 6), None));
+        assert!(items.iter().any(|label| label == "type"));
+        assert!(items.iter().any(|label| label == "waitkey"));
+
+        // Not after the first word
+        assert!(completion("wait ", Position::new(0, 5), None).is_empty());
+    }
+
+    #[test]
+    fn complete_markers() {
+        let source = "type \"fn main() {\n    // @body\n}\"\ninsert '// @top\n'\ngoto \ndelete";
+        let items = labels(completion(source, Position::new(5, 5), None));
+        assert_eq!(items, vec!["body", "top"]);
+    }
+
+    #[test]
+    fn complete_markers_in_loaded_files() {
+        let dir = std::env::temp_dir().join(format!("parrot-lsp-markers-in-loaded-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {\n    // @inside\n}\n").unwrap();
+
+        let source = "load \"main.rs\" as main\ntype main\ngoto in";
+        let items = labels(completion(source, Position::new(2, 7), Some(&dir)));
+        assert_eq!(items, vec!["inside"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hover_keyword() {
+        let source = "repeat 2 {\n    typenl \"a\"\n}";
+        let hover = hover(source, Position::new(1, 6)).unwrap();
+        let HoverContents::Markup(content) = hover.contents else { panic!() };
+        assert!(content.value.contains("typenl <ident>|<string> [nonl]"));
+        assert_eq!(hover.range, Some(Range::new(Position::new(1, 4), Position::new(1, 10))));
+
+        // Only instruction keywords
+        assert!(super::hover("type \"wait\"", Position::new(0, 7)).is_none());
+    }
+
+    #[test]
+    fn definition_of_loaded_name() {
+        let source = "load \"main.rs\" as main\nclear\n\ndefine a {\n    type main\n}";
+        let range = definition(source, Position::new(4, 10)).unwrap();
+        assert_eq!(range, Range::new(Position::new(0, 0), Position::new(0, 22)));
+
+        // Nothing for names that aren't typed
+        assert!(definition(source, Position::new(3, 7)).is_none());
+    }
+}
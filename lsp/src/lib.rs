@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, ProtocolError, Request, Response, ResponseError};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionOptions, CompletionParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, HoverParams, HoverProviderCapability,
+    Location, OneOf, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Url,
+};
+
+mod analysis;
+
+#[derive(Debug)]
+pub enum Error {
+    Protocol(ProtocolError),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+    Disconnected,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Protocol(error) => write!(f, "{error}"),
+            Error::Json(error) => write!(f, "{error}"),
+            Error::Io(error) => write!(f, "{error}"),
+            Error::Disconnected => write!(f, "the client disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ProtocolError> for Error {
+    fn from(e: ProtocolError) -> Self {
+        Self::Protocol(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Serve the language server protocol over stdin and stdout until the client shuts it down
+pub fn run() -> Result<(), Error> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![" ".into()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    Server::new(connection).serve()?;
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server {
+    connection: Connection,
+    // The text of every open document
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            documents: HashMap::new(),
+        }
+    }
+
+    fn serve(&mut self) -> Result<(), Error> {
+        let receiver = self.connection.receiver.clone();
+        for message in receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.request(request)?;
+                }
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // A request that can't be answered gets an error response, and the server carries on
+    fn request(&self, request: Request) -> Result<(), Error> {
+        let response = match self.respond(&request.method, request.params) {
+            Ok(result) => Response {
+                id: request.id,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => Response {
+                id: request.id,
+                result: None,
+                error: Some(error),
+            },
+        };
+        self.send(response.into())
+    }
+
+    fn respond(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, ResponseError> {
+        match method {
+            Completion::METHOD => {
+                let params: CompletionParams = serde_json::from_value(params).map_err(invalid_params)?;
+                let document = params.text_document_position;
+                let items = self.document(&document.text_document.uri).map(|source| {
+                    let dir = directory(&document.text_document.uri);
+                    analysis::completion(source, document.position, dir.as_deref())
+                });
+                serde_json::to_value(items).map_err(internal_error)
+            }
+            HoverRequest::METHOD => {
+                let params: HoverParams = serde_json::from_value(params).map_err(invalid_params)?;
+                let document = params.text_document_position_params;
+                let hover = self
+                    .document(&document.text_document.uri)
+                    .and_then(|source| analysis::hover(source, document.position));
+                serde_json::to_value(hover).map_err(internal_error)
+            }
+            GotoDefinition::METHOD => {
+                let params: GotoDefinitionParams = serde_json::from_value(params).map_err(invalid_params)?;
+                let document = params.text_document_position_params;
+                let uri = document.text_document.uri;
+                let definition = self
+                    .document(&uri)
+                    .and_then(|source| analysis::definition(source, document.position))
+                    .map(|range| GotoDefinitionResponse::Scalar(Location::new(uri.clone(), range)));
+                serde_json::to_value(definition).map_err(internal_error)
+            }
+            method => Err(response_error(
+                ErrorCode::MethodNotFound,
+                format!("unsupported request: {method}"),
+            )),
+        }
+    }
+
+    // Notifications can't be answered, so any that can't be read are ignored
+    fn notification(&mut self, notification: Notification) -> Result<(), Error> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<DidOpenTextDocumentParams>(notification.params) else {
+                    return Ok(());
+                };
+                self.update(params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<DidChangeTextDocumentParams>(notification.params) else {
+                    return Ok(());
+                };
+                // The whole document is sent on every change
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.update(params.text_document.uri, change.text),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<DidCloseTextDocumentParams>(notification.params) else {
+                    return Ok(());
+                };
+                self.documents.remove(&params.text_document.uri);
+                self.publish(params.text_document.uri, vec![])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Store the new text of the document and report the errors in it
+    fn update(&mut self, uri: Url, text: String) -> Result<(), Error> {
+        let diagnostics = analysis::diagnostics(&text);
+        self.documents.insert(uri.clone(), text);
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<(), Error> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.into(), params);
+        self.send(notification.into())
+    }
+
+    fn document(&self, uri: &Url) -> Option<&str> {
+        self.documents.get(uri).map(String::as_str)
+    }
+
+    fn send(&self, message: Message) -> Result<(), Error> {
+        self.connection.sender.send(message).map_err(|_| Error::Disconnected)
+    }
+}
+
+fn invalid_params(error: serde_json::Error) -> ResponseError {
+    response_error(ErrorCode::InvalidParams, error.to_string())
+}
+
+fn internal_error(error: serde_json::Error) -> ResponseError {
+    response_error(ErrorCode::InternalError, error.to_string())
+}
+
+fn response_error(code: ErrorCode, message: String) -> ResponseError {
+    ResponseError {
+        code: code as i32,
+        message,
+        data: None,
+    }
+}
+
+// The directory of the document, for loading files relative to it
+fn directory(uri: &Url) -> Option<PathBuf> {
+    let path = uri.to_file_path().ok()?;
+    path.parent().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn survive_bad_messages() {
+        let (connection, client) = Connection::memory();
+        let mut server = Server::new(connection);
+
+        let notification = Notification::new(DidOpenTextDocument::METHOD.into(), "nope");
+        server.notification(notification).unwrap();
+        assert!(client.receiver.try_recv().is_err());
+
+        let request = Request::new(1.into(), HoverRequest::METHOD.into(), "nope");
+        server.request(request).unwrap();
+        let Ok(Message::Response(response)) = client.receiver.try_recv() else { panic!() };
+        assert_eq!(response.error.unwrap().code, ErrorCode::InvalidParams as i32);
+    }
+}
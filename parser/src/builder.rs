@@ -0,0 +1,316 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::instruction::{Anchor, Dest, Instruction, Instructions, OnFinish, Source, Spanned};
+use crate::token::Span;
+
+/// Build a script in code rather than parsing one.
+/// Every method adds the instruction of the same name, so
+///
+/// ```text
+/// Script::new()
+///     .extension("rs")
+///     .speed(Duration::from_millis(65))
+///     .type_str("fn main() {\n}")
+///     .goto_marker("body")
+/// ```
+///
+/// is the same as the script
+///
+/// ```text
+/// extension "rs"
+/// speed 65
+/// type "fn main() {\n}"
+/// goto body
+/// ```
+///
+/// The finished script is compiled and played like a parsed one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Script {
+    instructions: Vec<Instruction>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add any instruction, for anything without a method of its own
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    pub fn load(self, path: impl Into<PathBuf>, name: impl Into<String>) -> Self {
+        self.instruction(Instruction::Load(path.into(), name.into()))
+    }
+
+    pub fn include(self, path: impl Into<PathBuf>) -> Self {
+        self.instruction(Instruction::Include(path.into()))
+    }
+
+    /// Bind a value to a name, like `let name = "value"`
+    pub fn bind(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.instruction(Instruction::Let {
+            name: name.into(),
+            value: value.into(),
+        })
+    }
+
+    pub fn find(self, needle: impl Into<String>) -> Self {
+        self.instruction(Instruction::Find(needle.into()))
+    }
+
+    pub fn goto_marker(self, marker: impl Into<String>) -> Self {
+        self.instruction(Instruction::Goto(Dest::Marker(marker.into())))
+    }
+
+    /// Move the cursor to a number of rows and columns away from a marker
+    pub fn goto_marker_offset(self, marker: impl Into<String>, row: i32, col: i32) -> Self {
+        self.instruction(Instruction::Goto(Dest::MarkerOffset {
+            name: marker.into(),
+            row,
+            col,
+        }))
+    }
+
+    /// Move the cursor by a number of rows and columns
+    pub fn goto_relative(self, row: i32, col: i32) -> Self {
+        self.instruction(Instruction::Goto(Dest::Relative { row, col }))
+    }
+
+    /// Move the cursor to a line and column, both counting from one
+    pub fn goto_line(self, line: u32, col: u32) -> Self {
+        self.instruction(Instruction::Goto(Dest::Absolute { line, col }))
+    }
+
+    pub fn goto_anchor(self, anchor: Anchor) -> Self {
+        self.instruction(Instruction::Goto(Dest::Anchor(anchor)))
+    }
+
+    pub fn type_str(self, text: impl Into<String>) -> Self {
+        self.print(Source::Str(text.into()), false)
+    }
+
+    /// Type the value bound to `name` by `load` or `bind`
+    pub fn type_ident(self, name: impl Into<String>) -> Self {
+        self.print(Source::Ident(name.into()), false)
+    }
+
+    pub fn typenl_str(self, text: impl Into<String>) -> Self {
+        self.print(Source::Str(text.into()), true)
+    }
+
+    pub fn typenl_ident(self, name: impl Into<String>) -> Self {
+        self.print(Source::Ident(name.into()), true)
+    }
+
+    fn print(self, source: Source, prefix_newline: bool) -> Self {
+        self.instruction(Instruction::Type {
+            source,
+            trim_trailing_newline: false,
+            prefix_newline,
+        })
+    }
+
+    pub fn insert_str(self, text: impl Into<String>) -> Self {
+        self.instruction(Instruction::Insert(Source::Str(text.into())))
+    }
+
+    pub fn insert_ident(self, name: impl Into<String>) -> Self {
+        self.instruction(Instruction::Insert(Source::Ident(name.into())))
+    }
+
+    pub fn replace_str(self, src: impl Into<String>, replacement: impl Into<String>) -> Self {
+        self.instruction(Instruction::Replace {
+            src: src.into(),
+            replacement: Source::Str(replacement.into()),
+        })
+    }
+
+    pub fn replace_ident(self, src: impl Into<String>, name: impl Into<String>) -> Self {
+        self.instruction(Instruction::Replace {
+            src: src.into(),
+            replacement: Source::Ident(name.into()),
+        })
+    }
+
+    pub fn delete(self) -> Self {
+        self.instruction(Instruction::Delete)
+    }
+
+    pub fn select(self, width: u16, height: u16) -> Self {
+        self.instruction(Instruction::Select { width, height })
+    }
+
+    pub fn title(self, title: impl Into<String>) -> Self {
+        self.instruction(Instruction::SetTitle(title.into()))
+    }
+
+    pub fn theme(self, theme: impl Into<String>) -> Self {
+        self.instruction(Instruction::SetTheme(theme.into()))
+    }
+
+    pub fn extension(self, extension: impl Into<String>) -> Self {
+        self.instruction(Instruction::SetExtension(extension.into()))
+    }
+
+    pub fn line_numbers(self, show: bool) -> Self {
+        self.instruction(Instruction::ShowLineNumbers(show))
+    }
+
+    /// The time between key presses
+    pub fn speed(self, duration: Duration) -> Self {
+        self.instruction(Instruction::Speed(duration))
+    }
+
+    /// The longest random delay added to every key press
+    pub fn jitter(self, duration: Duration) -> Self {
+        self.instruction(Instruction::Jitter(duration))
+    }
+
+    /// The time to wait after every newline
+    pub fn line_pause(self, duration: Duration) -> Self {
+        self.instruction(Instruction::LinePause(duration))
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        self.instruction(Instruction::Seed(seed))
+    }
+
+    pub fn audio(self, path: impl Into<PathBuf>) -> Self {
+        self.instruction(Instruction::LoadAudio(path.into()))
+    }
+
+    pub fn popup(self, message: impl Into<String>) -> Self {
+        self.instruction(Instruction::Popup(message.into()))
+    }
+
+    pub fn close_popup(self) -> Self {
+        self.instruction(Instruction::ClosePopup)
+    }
+
+    pub fn clear(self) -> Self {
+        self.instruction(Instruction::Clear)
+    }
+
+    pub fn wait(self, duration: Duration) -> Self {
+        self.instruction(Instruction::Wait(duration))
+    }
+
+    /// Wait for a key press
+    pub fn pause(self) -> Self {
+        self.instruction(Instruction::Pause(None))
+    }
+
+    /// Wait for a key press, showing the hint in the status bar
+    pub fn pause_with(self, hint: impl Into<String>) -> Self {
+        self.instruction(Instruction::Pause(Some(hint.into())))
+    }
+
+    pub fn chapter(self, name: impl Into<String>) -> Self {
+        self.instruction(Instruction::Chapter(name.into()))
+    }
+
+    pub fn on_finish(self, on_finish: OnFinish) -> Self {
+        self.instruction(Instruction::OnFinish(on_finish))
+    }
+
+    /// Define a block of instructions that can be played with `call`
+    pub fn define<P: Into<String>>(
+        self,
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = P>,
+        body: Script,
+    ) -> Self {
+        self.instruction(Instruction::Define {
+            name: name.into(),
+            params: params.into_iter().map(Into::into).collect(),
+            body: body.spanned(),
+        })
+    }
+
+    pub fn call<A: Into<String>>(self, name: impl Into<String>, args: impl IntoIterator<Item = A>) -> Self {
+        self.instruction(Instruction::Call {
+            name: name.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    pub fn repeat(self, count: u64, body: Script) -> Self {
+        self.instruction(Instruction::Repeat {
+            count,
+            body: body.spanned(),
+        })
+    }
+
+    /// The instructions of the script, ready to be compiled
+    pub fn build(self) -> Instructions {
+        Instructions::new(self.spanned(), "")
+    }
+
+    // The instructions weren't parsed from anything, so they all point at the start
+    fn spanned(self) -> Vec<Spanned> {
+        self.instructions
+            .into_iter()
+            .map(|instruction| Spanned {
+                instruction,
+                start: Span::INITIAL,
+                end: Span::INITIAL,
+            })
+            .collect()
+    }
+}
+
+impl From<Script> for Instructions {
+    fn from(script: Script) -> Self {
+        script.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_like_parse() {
+        let source = r#"
+extension "rs"
+speed 65
+load "main.rs" as main
+type main
+goto body
+typenl "let x = 1;"
+define greet name {
+    type "{name}"
+}
+repeat 2 {
+    call greet "you"
+    wait 1s
+}
+onfinish exit
+"#;
+        let parsed = crate::parse(source).unwrap();
+
+        let built = Script::new()
+            .extension("rs")
+            .speed(Duration::from_millis(65))
+            .load("main.rs", "main")
+            .type_ident("main")
+            .goto_marker("body")
+            .typenl_str("let x = 1;")
+            .define("greet", ["name"], Script::new().type_str("{name}"))
+            .repeat(2, Script::new().call("greet", ["you"]).wait(Duration::from_secs(1)))
+            .on_finish(OnFinish::Exit)
+            .build();
+
+        let unspan = |instructions: Instructions| {
+            instructions
+                .take_instructions()
+                .into_iter()
+                .map(Spanned::unspan)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(unspan(built), unspan(parsed));
+    }
+}
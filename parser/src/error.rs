@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter};
 
+use unicode_width::UnicodeWidthStr;
+
 use crate::token::{Span, Token};
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -7,9 +9,11 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
-    start: Span,
+    pub(crate) start: Span,
     end: Span,
     source: String,
+    // The syntax of the instruction the error is in
+    help: Option<&'static str>,
 }
 
 impl Error {
@@ -19,10 +23,30 @@ impl Error {
             start,
             end,
             source: source.into(),
+            help: None,
         };
         Err(error)
     }
 
+    /// The start of the error, and the start of whatever comes after it
+    pub fn span(&self) -> (Span, Span) {
+        (self.start, self.end)
+    }
+
+    /// What went wrong, without the source around it
+    pub fn message(&self) -> String {
+        match self.help {
+            Some(help) => format!("{}\nhelp: {help}", self.kind),
+            None => self.kind.to_string(),
+        }
+    }
+
+    /// Show the syntax of the instruction, unless a more specific help is already set
+    pub(crate) fn with_help(mut self, help: Option<&'static str>) -> Self {
+        self.help = self.help.or(help);
+        self
+    }
+
     // -----------------------------------------------------------------------------
     //   - Lex errors -
     // -----------------------------------------------------------------------------
@@ -34,11 +58,28 @@ impl Error {
         Self::err(ErrorKind::UnterminatedString, (span, span), source)
     }
 
+    pub(crate) fn invalid_duration<T>(span: Span, source: impl Into<String>) -> Result<T> {
+        Self::err(ErrorKind::InvalidDuration, (span, span), source)
+    }
+
+    pub(crate) fn invalid_escape<T>(span: Span, source: impl Into<String>) -> Result<T> {
+        Self::err(ErrorKind::InvalidEscape, (span, span), source)
+    }
+
+    pub(crate) fn invalid_heredoc<T>(span: Span, source: impl Into<String>) -> Result<T> {
+        Self::err(ErrorKind::InvalidHeredoc, (span, span), source)
+    }
+
     // -----------------------------------------------------------------------------
     //   - Parse errors -
     // -----------------------------------------------------------------------------
-    pub(crate) fn invalid_instruction<T>(token: Token, spans: (Span, Span), source: impl Into<String>) -> Result<T> {
-        Self::err(ErrorKind::InvalidInstruction(token), spans, source)
+    pub(crate) fn invalid_instruction<T>(
+        token: Token,
+        suggestion: Option<&'static str>,
+        spans: (Span, Span),
+        source: impl Into<String>,
+    ) -> Result<T> {
+        Self::err(ErrorKind::InvalidInstruction { token, suggestion }, spans, source)
     }
 
     pub(crate) fn invalid_arg<T>(
@@ -74,30 +115,117 @@ impl Error {
     }
 }
 
+// Use the alternate flag (`{:#}`) to colour the output
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        static MAX_LINES: u16 = 3;
-        let from = self.start.line.saturating_sub(2) as usize;
-        let to = MAX_LINES.max(self.end.line - self.start.line + 1) as usize;
+        render(f, &self.source, (self.start, self.end), &self.kind, self.help)
+    }
+}
+
+/// Write the lines of `source` leading up to `start`, with everything from
+/// `start` to `end` underlined and labelled with the message.
+/// This is how parse errors are shown, and the alternate flag (`{:#}`) colours it the same way.
+pub fn snippet(f: &mut Formatter<'_>, source: &str, start: Span, end: Span, message: &dyn Display) -> std::fmt::Result {
+    render(f, source, (start, end), message, None)
+}
+
+fn render(
+    f: &mut Formatter<'_>,
+    source: &str,
+    (start, end): (Span, Span),
+    message: &dyn Display,
+    help: Option<&str>,
+) -> std::fmt::Result {
+    static MAX_LINES: u16 = 3;
+    let (red, blue, bold, reset) = match f.alternate() {
+        true => ("\x1b[1;31m", "\x1b[34m", "\x1b[1m", "\x1b[0m"),
+        false => ("", "", "", ""),
+    };
+
+    let from = start.line.saturating_sub(2) as usize;
+    let to = MAX_LINES.max(end.line.saturating_sub(start.line) + 1) as usize;
+
+    let lines = source.lines().enumerate().skip(from).take(to);
+
+    let row_width = (from + to).to_string().len();
+
+    writeln!(f)?;
+    for (no, line) in lines {
+        let gutter = format!("{:>row_width$}: ", no + 1);
+        writeln!(f, "{blue}{gutter}{reset}{line}")?;
+        if no + 1 != start.line as usize {
+            continue;
+        }
+
+        // Underline the whole range, or the rest of the line if the range
+        // spans several lines
+        let from = start.col as usize;
+        let to = match end.line == start.line {
+            true => end.col as usize,
+            false => line.width() + 1,
+        };
+        let underline = "^".repeat(to.saturating_sub(from).max(1));
+        let indent = " ".repeat(gutter.len() + from - 1);
+        writeln!(f, "{indent}{red}{underline} {message}{reset}")?;
+
+        if let Some(help) = help {
+            let indent = " ".repeat(gutter.len());
+            writeln!(f, "{indent}{bold}help:{reset} {help}")?;
+        }
+    }
+    Ok(())
+}
 
-        let lines = self.source.lines().enumerate().skip(from).take(to);
+impl std::error::Error for Error {
+}
 
-        let row_width = (from + to - 1).to_string().len();
+/// Every error found while parsing a script
+#[derive(Debug)]
+pub struct Errors {
+    inner: Vec<Error>,
+}
+
+impl Errors {
+    pub(crate) fn new(inner: Vec<Error>) -> Self {
+        Self { inner }
+    }
+
+    pub(crate) fn into_inner(self) -> Vec<Error> {
+        self.inner
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
 
-        writeln!(f)?;
-        for (no, line) in lines {
-            let gutter = format!("{:>row_width$}: ", no + 1);
-            writeln!(f, "{gutter}{line}")?;
-            if no + 1 == self.start.line as usize {
-                let indent = " ".repeat(gutter.len());
-                writeln!(f, "{indent}^-- {}", self.kind)?;
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Error> {
+        self.inner.iter()
+    }
+}
+
+impl From<Error> for Errors {
+    fn from(error: Error) -> Self {
+        Self::new(vec![error])
+    }
+}
+
+impl Display for Errors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for error in &self.inner {
+            match f.alternate() {
+                true => write!(f, "{error:#}")?,
+                false => write!(f, "{error}")?,
             }
         }
         Ok(())
     }
 }
 
-impl std::error::Error for Error {
+impl std::error::Error for Errors {
 }
 
 #[derive(Debug)]
@@ -105,11 +233,23 @@ pub enum ErrorKind {
     // Lex errors
     UnterminatedString,
     InvalidInteger,
+    InvalidDuration,
+    InvalidEscape,
+    InvalidHeredoc,
 
     // Parse errors
-    InvalidArg { expected: &'static str, found: String },
-    InvalidInstruction(Token),
-    UnexpectedToken { expected: &'static str, found: String },
+    InvalidArg {
+        expected: &'static str,
+        found: String,
+    },
+    InvalidInstruction {
+        token: Token,
+        suggestion: Option<&'static str>,
+    },
+    UnexpectedToken {
+        expected: &'static str,
+        found: String,
+    },
 }
 
 impl Display for ErrorKind {
@@ -117,8 +257,20 @@ impl Display for ErrorKind {
         match self {
             ErrorKind::UnterminatedString => write!(f, "unterminated string"),
             ErrorKind::InvalidInteger => write!(f, "invalid integer"),
+            ErrorKind::InvalidDuration => write!(f, "invalid duration, expected a unit of `ms`, `s` or `m`"),
+            ErrorKind::InvalidEscape => write!(f, "invalid unicode escape, expected `\\u{{..}}`"),
+            ErrorKind::InvalidHeredoc => {
+                write!(f, "invalid heredoc, expected `<<NAME` followed by a newline")
+            }
             ErrorKind::InvalidArg { expected, found } => write!(f, "expected `{expected}`, found `{found}`"),
-            ErrorKind::InvalidInstruction(token) => write!(f, "invalid instruction: `{token}`"),
+            ErrorKind::InvalidInstruction {
+                token,
+                suggestion: None,
+            } => write!(f, "invalid instruction: `{token}`"),
+            ErrorKind::InvalidInstruction {
+                token,
+                suggestion: Some(suggestion),
+            } => write!(f, "invalid instruction: `{token}`, did you mean `{suggestion}`?"),
             ErrorKind::UnexpectedToken { expected, found } => {
                 write!(f, "unexpected token, `{expected}`, found `{found}`")
             }
@@ -0,0 +1,214 @@
+use crate::error::Errors;
+use crate::lexer::lex;
+use crate::token::Token;
+
+static INDENT: &str = "    ";
+
+// A line of the script, as the text of every token on it
+#[derive(Default)]
+struct Line<'src> {
+    words: Vec<String>,
+    comment: Option<&'src str>,
+    // Closing a block on the first token of the line outdents the line itself
+    starts_with_close: bool,
+    opens: usize,
+    closes: usize,
+}
+
+impl Line<'_> {
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.comment.is_none()
+    }
+}
+
+/// Format a script: one space between arguments, blocks indented by four spaces,
+/// double quoted strings (unless that takes more escapes than single quotes)
+/// and at most one blank line in a row.
+/// Comments, raw strings and heredocs are kept as they are.
+///
+/// Only a script without errors is formatted.
+pub fn format(source: &str) -> Result<String, Errors> {
+    crate::parse(source)?;
+
+    let (tokens, spans) = lex(source)?.into_parts();
+    let lexemes = spans.iter().enumerate().map(|(index, span)| {
+        let end = spans.get(index + 1).map_or(source.len(), |next| next.offset as usize);
+        &source[span.offset as usize..end]
+    });
+
+    let mut lines = vec![Line::default()];
+    for (token, lexeme) in tokens.iter().zip(lexemes) {
+        let line = lines.last_mut().expect("there is always a line");
+        match token {
+            Token::Newline => lines.push(Line::default()),
+            Token::Eof => break,
+            Token::Comment => line.comment = Some(lexeme.trim_end()),
+            // Anything the lexer doesn't know, like the comma in `goto 1, 2`, is
+            // whitespace to the parser but is kept next to the word before it
+            Token::Whitespace => match (lexeme.trim(), line.words.last_mut()) {
+                ("", _) => {}
+                (punctuation, Some(word)) => word.push_str(punctuation),
+                (punctuation, None) => line.words.push(punctuation.into()),
+            },
+            Token::Str(_) if lexeme.starts_with(['"', '\'']) => line.words.push(requote(lexeme)),
+            token => {
+                match token {
+                    Token::LeftBrace => line.opens += 1,
+                    Token::RightBrace if line.words.is_empty() => line.starts_with_close = true,
+                    Token::RightBrace => line.closes += 1,
+                    _ => {}
+                }
+                line.words.push(lexeme.into());
+            }
+        }
+    }
+
+    let mut output = String::new();
+    let mut depth = 0usize;
+    let mut blank = false;
+    for line in lines {
+        if line.starts_with_close {
+            depth = depth.saturating_sub(1);
+        }
+
+        if line.is_empty() {
+            blank = !output.is_empty();
+            continue;
+        }
+
+        if blank {
+            output.push('\n');
+            blank = false;
+        }
+
+        output.push_str(&INDENT.repeat(depth));
+        output.push_str(&line.words.join(" "));
+        if let Some(comment) = line.comment {
+            if !line.words.is_empty() {
+                output.push(' ');
+            }
+            output.push_str(comment);
+        }
+        output.push('\n');
+
+        depth = (depth + line.opens).saturating_sub(line.closes);
+    }
+
+    Ok(output)
+}
+
+// A piece of the content of a quoted string
+enum Piece {
+    Char(char),
+    // The character after a backslash, for any escape but a quote
+    Escape(char),
+}
+
+// Quote a string with double quotes, unless single quotes would need fewer escapes.
+// Every escape other than an escaped quote is kept as it is.
+fn requote(lexeme: &str) -> String {
+    let inner = &lexeme[1..lexeme.len() - 1];
+
+    let mut pieces = vec![];
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let piece = match c {
+            '\\' => match chars.next() {
+                Some(quote @ ('"' | '\'')) => Piece::Char(quote),
+                Some(c) => Piece::Escape(c),
+                None => Piece::Char('\\'),
+            },
+            c => Piece::Char(c),
+        };
+        pieces.push(piece);
+    }
+
+    let count = |quote| {
+        pieces
+            .iter()
+            .filter(|piece| matches!(piece, Piece::Char(c) if *c == quote))
+            .count()
+    };
+    let quote = match count('"') > count('\'') {
+        true => '\'',
+        false => '"',
+    };
+
+    let mut output = String::from(quote);
+    for piece in pieces {
+        match piece {
+            Piece::Char(c) if c == quote => output.extend(['\\', c]),
+            Piece::Char(c) => output.push(c),
+            Piece::Escape(c) => output.extend(['\\', c]),
+        }
+    }
+    output.push(quote);
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_format(input: &str, expected: &str) {
+        let output = format(input).unwrap();
+        assert_eq!(output, expected);
+        // Formatting is idempotent
+        assert_eq!(format(&output).unwrap(), expected);
+    }
+
+    #[test]
+    fn normalise_whitespace() {
+        assert_format(
+            "\n\n  clear\ngoto    1,   2\n\n\n\nwait\t2s   \nlet  x =  \"a  b\"\n\n",
+            "clear\ngoto 1, 2\n\nwait 2s\nlet x = \"a  b\"\n",
+        );
+    }
+
+    #[test]
+    fn keep_comments() {
+        assert_format(
+            "// intro   \n   clear   // reset\n\n  // end",
+            "// intro\nclear // reset\n\n// end\n",
+        );
+    }
+
+    #[test]
+    fn indent_blocks() {
+        assert_format(
+            "define greet name {\ntype \"{name}\"\n      repeat 2 {\n delete\n}\n  }\ncall greet \"you\"",
+            "define greet name {\n    type \"{name}\"\n    repeat 2 {\n        delete\n    }\n}\ncall greet \"you\"\n",
+        );
+    }
+
+    #[test]
+    fn double_quote_strings() {
+        assert_format(
+            "type 'it\\'s'\ntype \"\\'a\\'\\n\\u{1F99C}\"\ntype r#\"raw \"one\"\"#",
+            "type \"it's\"\ntype \"'a'\\n\\u{1F99C}\"\ntype r#\"raw \"one\"\"#\n",
+        );
+
+        // Unless that takes more escapes
+        assert_format("type 'say \"hi\"'", "type 'say \"hi\"'\n");
+        assert_format("type \"say \\\"hi\\\"\"", "type 'say \"hi\"'\n");
+        assert_format("type 'it\\'s \"hi\"'", "type 'it\\'s \"hi\"'\n");
+    }
+
+    #[test]
+    fn keep_heredocs() {
+        assert_format(
+            "define a {\ntype <<EOF\n  fn main() {\n  }\n  EOF\n}",
+            "define a {\n    type <<EOF\n  fn main() {\n  }\n  EOF\n}\n",
+        );
+    }
+
+    #[test]
+    fn keep_signed_offsets() {
+        assert_format("goto here +2   -4", "goto here +2 -4\n");
+    }
+
+    #[test]
+    fn refuse_invalid_scripts() {
+        assert!(format("wait nope").is_err());
+    }
+}
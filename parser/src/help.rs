@@ -0,0 +1,192 @@
+use crate::token::Token;
+
+// Every keyword that starts an instruction
+const INSTRUCTIONS: &[(&str, Token)] = &[
+    ("audio", Token::Audio),
+    ("call", Token::Call),
+    ("chapter", Token::Chapter),
+    ("clear", Token::Clear),
+    ("closepopup", Token::ClosePopup),
+    ("define", Token::Define),
+    ("delete", Token::Delete),
+    ("extension", Token::SetExtension),
+    ("find", Token::Find),
+    ("goto", Token::Goto),
+    ("include", Token::Include),
+    ("insert", Token::Insert),
+    ("jitter", Token::Jitter),
+    ("let", Token::Let),
+    ("linepause", Token::LinePause),
+    ("load", Token::Load),
+    ("numbers", Token::ShowLineNumbers),
+    ("onfinish", Token::OnFinish),
+    ("pause", Token::Pause),
+    ("popup", Token::Popup),
+    ("repeat", Token::Repeat),
+    ("replace", Token::Replace),
+    ("seed", Token::Seed),
+    ("select", Token::Select),
+    ("sleep", Token::Wait),
+    ("speed", Token::Speed),
+    ("theme", Token::Theme),
+    ("title", Token::SetTitle),
+    ("type", Token::Type),
+    ("typenl", Token::TypeNl),
+    ("wait", Token::Wait),
+    ("waitkey", Token::Pause),
+];
+
+/// An instruction keyword, for completion and documentation in editors
+#[derive(Debug, Copy, Clone)]
+pub struct Keyword {
+    pub name: &'static str,
+    pub syntax: &'static str,
+    pub description: &'static str,
+}
+
+/// Every keyword that starts an instruction, including aliases
+pub fn keywords() -> impl Iterator<Item = Keyword> {
+    INSTRUCTIONS.iter().filter_map(|(name, token)| {
+        Some(Keyword {
+            name,
+            syntax: syntax(token)?,
+            description: description(token)?,
+        })
+    })
+}
+
+/// The keyword with the given name
+pub fn keyword(name: &str) -> Option<Keyword> {
+    keywords().find(|keyword| keyword.name == name)
+}
+
+/// The expected syntax of the instruction starting with `token`
+pub(crate) fn syntax(token: &Token) -> Option<&'static str> {
+    let syntax = match token {
+        Token::Audio => "audio <filepath>",
+        Token::Call => "call <ident> [<string>...]",
+        Token::Chapter => "chapter <name>|<string>",
+        Token::Clear => "clear",
+        Token::ClosePopup => "closepopup",
+        Token::Define => "define <ident> [<param>...] { ... }",
+        Token::Delete => "delete",
+        Token::SetExtension => "extension <string>",
+        Token::Find => "find <string>",
+        Token::Goto => "goto <marker> [<row> <col>]|<row> <col>|line <int> [col <int>]|end|bol|eol|indent",
+        Token::Include => "include <filepath>",
+        Token::Insert => "insert <ident>|<string>",
+        Token::Jitter => "jitter <milliseconds>|<duration>",
+        Token::Let => "let <ident> = <string>",
+        Token::LinePause => "linepause <milliseconds>|<duration>",
+        Token::Load => "load <filepath> as <ident>",
+        Token::ShowLineNumbers => "numbers <true|false>",
+        Token::OnFinish => "onfinish <hold|loop|exit>",
+        Token::Pause => "pause [<string>]",
+        Token::Popup => "popup <string>",
+        Token::Repeat => "repeat <int> { ... }",
+        Token::Replace => "replace <string> <ident>|<string>",
+        Token::Seed => "seed <int>",
+        Token::Select => "select <width> <height>",
+        Token::Speed => "speed <milliseconds>|<duration>",
+        Token::Theme => "theme <string>",
+        Token::SetTitle => "title <string>",
+        Token::Type => "type <ident>|<string>",
+        Token::TypeNl => "typenl <ident>|<string> [nonl]",
+        Token::Wait => "wait <seconds>|<duration>",
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// The instruction keyword closest to `word`, if any is close enough
+/// to be a likely typo.
+pub(crate) fn suggest(word: &str) -> Option<(&'static str, &'static Token)> {
+    let max = 2.min(word.chars().count().saturating_sub(1));
+    INSTRUCTIONS
+        .iter()
+        .map(|(keyword, token)| (distance(word, keyword), keyword, token))
+        .filter(|(distance, ..)| *distance <= max)
+        .min_by_key(|(distance, ..)| *distance)
+        .map(|(_, keyword, token)| (*keyword, token))
+}
+
+// What the instruction starting with `token` does
+fn description(token: &Token) -> Option<&'static str> {
+    let description = match token {
+        Token::Audio => "Load the typing sounds from a directory, which needs a `default.mp3`.",
+        Token::Call => "Play a block of instructions created with `define`, binding its parameters to the arguments.",
+        Token::Chapter => "Name a point in the script that playback can fast forward to.",
+        Token::Clear => "Clear the screen.",
+        Token::ClosePopup => "Close the popup message.",
+        Token::Define => "Create a named block of instructions that can be played with `call`.",
+        Token::Delete => "Delete the selected region, or the character under the cursor.",
+        Token::SetExtension => "Set the file extension for the syntax highlighter.",
+        Token::Find => "Move the cursor to the text on the current line.",
+        Token::Goto => "Move the cursor to a marker, a line and column, an anchor, or by a number of rows and columns.",
+        Token::Include => "Play another script in place of this instruction.",
+        Token::Insert => "Insert text at once, without typing it out.",
+        Token::Jitter => "Add a random delay of up to this long to every key press.",
+        Token::Let => "Bind a string to a name, available as `{name}` in strings (`{{name}}` types it as is).",
+        Token::LinePause => "Wait this long after every newline that is typed.",
+        Token::Load => "Load a file into memory under a name.",
+        Token::ShowLineNumbers => "Show or hide line numbers.",
+        Token::OnFinish => "Choose what happens once the script is done.",
+        Token::Pause => "Stop the playback until a key is pressed, optionally with a hint.",
+        Token::Popup => "Show a popup message.",
+        Token::Repeat => "Play a block of instructions a number of times.",
+        Token::Replace => "Select, delete and retype text on the current line.",
+        Token::Seed => "Seed the random jitter so the timing is the same on every run.",
+        Token::Select => "Select text from the cursor, given a width and a height.",
+        Token::Speed => "Set the time between key presses.",
+        Token::Theme => "Set the theme for the syntax highlighter.",
+        Token::SetTitle => "Set the title in the status bar.",
+        Token::Type => "Type out text in the editor.",
+        Token::TypeNl => "Start a new line below the cursor and type out text on it.",
+        Token::Wait => "Wait before the next instruction.",
+        _ => return None,
+    };
+    Some(description)
+}
+
+// Levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut row = vec![0; b.len() + 1];
+
+    for (i, a) in a.chars().enumerate() {
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let cost = usize::from(a != *b);
+            row[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lexer::lex;
+
+    #[test]
+    fn keywords_lex_to_their_token() {
+        for (keyword, token) in INSTRUCTIONS {
+            let tokens = lex(keyword).unwrap().take_tokens();
+            assert_eq!(&tokens[0], token, "{keyword}");
+            assert!(syntax(token).is_some(), "{keyword}");
+            assert!(description(token).is_some(), "{keyword}");
+        }
+    }
+
+    #[test]
+    fn suggest_closest_keyword() {
+        assert_eq!(suggest("typnl").unwrap().0, "typenl");
+        assert_eq!(suggest("wiat").unwrap().0, "wait");
+        assert_eq!(suggest("chaptr").unwrap().0, "chapter");
+        assert!(suggest("bogus").is_none());
+        assert!(suggest("x").is_none());
+    }
+}
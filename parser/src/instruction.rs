@@ -1,9 +1,62 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Debug, PartialEq)]
+use crate::token::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Dest {
-    Relative { row: i32, col: i32 },
+    Relative {
+        row: i32,
+        col: i32,
+    },
     Marker(String),
+    /// A number of rows and columns away from a marker
+    MarkerOffset {
+        name: String,
+        row: i32,
+        col: i32,
+    },
+    /// A line and column in the document, both counting from one
+    Absolute {
+        line: u32,
+        col: u32,
+    },
+    Anchor(Anchor),
+}
+
+/// A position that depends on the document, or on the line the cursor is on
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Anchor {
+    /// The end of the last line
+    End,
+    /// The start of the current line
+    LineStart,
+    /// The end of the current line
+    LineEnd,
+    /// The first character on the current line that isn't whitespace
+    Indent,
+}
+
+impl Dest {
+    /// Whether `goto <name>` means something other than the marker called `name`
+    pub fn is_reserved(name: &str) -> bool {
+        name == "line" || Anchor::from_name(name).is_some()
+    }
+}
+
+impl Anchor {
+    /// The anchor named after `goto`, like `end` in `goto end`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "end" => Some(Self::End),
+            "bol" => Some(Self::LineStart),
+            "eol" => Some(Self::LineEnd),
+            "indent" => Some(Self::Indent),
+            _ => None,
+        }
+    }
 }
 
 impl From<(i32, i32)> for Dest {
@@ -18,15 +71,37 @@ impl From<&str> for Dest {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Source {
     Str(String),
     Ident(String),
 }
 
-#[derive(Debug, PartialEq)]
+/// What happens once every instruction has been played
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OnFinish {
+    /// Keep showing the document until the user quits
+    #[default]
+    Hold,
+    /// Clear the document and play the script again
+    Loop,
+    /// Return to the shell
+    Exit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Load(PathBuf, String),
+    /// Play another script in place of this instruction
+    Include(PathBuf),
+    /// Bind a value to a name, for use as `{name}` in strings
+    Let {
+        name: String,
+        value: String,
+    },
     Find(String),
     Goto(Dest),
     Type {
@@ -35,7 +110,7 @@ pub enum Instruction {
         prefix_newline: bool,
     },
     Insert(Source),
-    Jitter(u64),
+    Jitter(Duration),
     Delete,
 
     /// This instructions requires that the cursor is placed on the
@@ -61,34 +136,109 @@ pub enum Instruction {
     SetTheme(String),
     SetExtension(String),
     ShowLineNumbers(bool),
-    LinePause(u64),
-    Speed(u64),
+    LinePause(Duration),
+    Speed(Duration),
+    /// Seed the random number generator used for jitter
+    Seed(u64),
     LoadAudio(PathBuf),
     Popup(String),
     ClosePopup,
     Clear,
-    Wait(u64),
+    Wait(Duration),
+    /// Wait for a key press, optionally showing a hint in the status bar
+    Pause(Option<String>),
+    /// A named point in the script that playback can skip ahead to
+    Chapter(String),
+    OnFinish(OnFinish),
+    /// A named block of instructions, expanded wherever it is called.
+    /// Every parameter is available as `{param}` inside the block.
+    Define {
+        name: String,
+        params: Vec<String>,
+        body: Vec<Spanned>,
+    },
+    Call {
+        name: String,
+        args: Vec<String>,
+    },
+    /// Play the block `count` times
+    Repeat {
+        count: u64,
+        body: Vec<Spanned>,
+    },
+}
+
+/// An instruction, and the range of the script it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spanned {
+    pub instruction: Instruction,
+    // Instructions written by hand, rather than parsed, can leave out where they came from
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub start: Span,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub end: Span,
+}
+
+#[cfg(test)]
+impl Spanned {
+    // The instruction with the spans reset, in blocks too, so instructions
+    // from different sources can be compared
+    pub(crate) fn unspan(self) -> Instruction {
+        let block = |body: Vec<Spanned>| {
+            body.into_iter()
+                .map(|spanned| Spanned {
+                    instruction: spanned.unspan(),
+                    start: Span::INITIAL,
+                    end: Span::INITIAL,
+                })
+                .collect()
+        };
+        match self.instruction {
+            Instruction::Define { name, params, body } => Instruction::Define {
+                name,
+                params,
+                body: block(body),
+            },
+            Instruction::Repeat { count, body } => Instruction::Repeat {
+                count,
+                body: block(body),
+            },
+            instruction => instruction,
+        }
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instructions {
-    inner: Vec<Instruction>,
+    #[cfg_attr(feature = "serde", serde(rename = "instructions"))]
+    inner: Vec<Spanned>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    source: String,
 }
 
 impl Instructions {
-    pub fn new(inner: Vec<Instruction>) -> Self {
-        Self { inner }
+    pub fn new(inner: Vec<Spanned>, source: impl Into<String>) -> Self {
+        Self {
+            inner,
+            source: source.into(),
+        }
+    }
+
+    /// The script the instructions were parsed from
+    pub fn source(&self) -> &str {
+        &self.source
     }
 
-    #[cfg(test)]
-    pub fn take_instructions(self) -> Vec<Instruction> {
+    pub fn take_instructions(self) -> Vec<Spanned> {
         self.inner
     }
 }
 
 impl IntoIterator for Instructions {
-    type IntoIter = <Vec<Instruction> as IntoIterator>::IntoIter;
-    type Item = Instruction;
+    type IntoIter = <Vec<Spanned> as IntoIterator>::IntoIter;
+    type Item = Spanned;
 
     fn into_iter(self) -> Self::IntoIter {
         self.inner.into_iter()
@@ -1,12 +1,24 @@
 use std::iter::Peekable;
 use std::str::Chars;
+use std::time::Duration;
 
 use unicode_width::UnicodeWidthChar;
 
 use crate::error::{Error, Result};
 use crate::token::{Span, Token, Tokens};
 
+/// Lex the code, stopping at the first error
 pub fn lex<'a>(code: &'a str) -> Result<Tokens<'a>> {
+    let (tokens, mut errors) = lex_all(code);
+    match errors.is_empty() {
+        true => Ok(tokens),
+        false => Err(errors.remove(0)),
+    }
+}
+
+/// Lex the code, carrying on past errors.
+/// The rest of the line after an error is skipped.
+pub fn lex_all(code: &str) -> (Tokens<'_>, Vec<Error>) {
     Lexer::new(code).lex()
 }
 
@@ -17,6 +29,7 @@ struct Lexer<'src> {
     spans: Vec<Span>,
     current_span: Span,
     next_span: Span,
+    errors: Vec<Error>,
 }
 
 impl<'src> Lexer<'src> {
@@ -28,6 +41,7 @@ impl<'src> Lexer<'src> {
             spans: vec![],
             current_span: Span::INITIAL,
             next_span: Span::INITIAL,
+            errors: vec![],
         }
     }
 
@@ -37,6 +51,7 @@ impl<'src> Lexer<'src> {
             .next()
             .expect("every character has to be checked before consume is called");
 
+        self.next_span.offset += c.len_utf8() as u32;
         if c == '\n' {
             self.next_span.line += 1;
             self.next_span.col = 1;
@@ -46,125 +61,294 @@ impl<'src> Lexer<'src> {
     }
 
     fn single_char_token(&mut self, token: Token) {
-        if token == Token::Newline {
-            self.next_span.line += 1;
-            self.next_span.col = 1;
-        } else {
-            self.next_span.col += 1;
-        }
         self.push_token(token);
     }
 
     fn multi_char_token(&mut self, token: Token) {
-        _ = self.input.next();
-        self.next_span.col += 2;
+        self.consume_char();
         self.push_token(token);
     }
 
-    fn lex(mut self) -> Result<Tokens<'src>> {
-        loop {
-            let Some(c) = self.input.next() else { break };
+    fn lex(mut self) -> (Tokens<'src>, Vec<Error>) {
+        while let Some(c) = self.input.peek().copied() {
+            // Every token starts with this character
+            self.consume_char();
 
-            match c {
-                // -----------------------------------------------------------------------------
-                //   - Multi char tokens-
-                // -----------------------------------------------------------------------------
-                '/' if Some('/') == self.input.peek().copied() => self.comment(),
-                '@' if Some('@') == self.input.peek().copied() => self.multi_char_token(Token::AtAt),
-
-                // -----------------------------------------------------------------------------
-                //   - Single char tokens -
-                // -----------------------------------------------------------------------------
-                '\n' => self.single_char_token(Token::Newline),
-                '=' => self.single_char_token(Token::Equal),
-                '@' => self.single_char_token(Token::At),
-                '!' => self.single_char_token(Token::Bang),
-
-                '-' | '0'..='9' => self.int(c)?,
-                'a'..='z' | 'A'..='Z' => self.ident(c)?,
-                '"' | '\'' => self.string(c)?,
-                _ => self.whitespace(),
+            if let Err(error) = self.token(c) {
+                self.errors.push(error);
+                self.skip_line();
             }
         }
 
         self.push_token(Token::Eof);
 
         let tokens = Tokens::new(self.source, self.tokens, self.spans);
-        Ok(tokens)
+        (tokens, self.errors)
+    }
+
+    // Lex the token starting with the character that was just consumed
+    fn token(&mut self, c: char) -> Result<()> {
+        match c {
+            // -----------------------------------------------------------------------------
+            //   - Multi char tokens-
+            // -----------------------------------------------------------------------------
+            '/' if Some('/') == self.input.peek().copied() => self.comment(),
+            '@' if Some('@') == self.input.peek().copied() => self.multi_char_token(Token::AtAt),
+            '<' if Some('<') == self.input.peek().copied() => self.heredoc()?,
+            'r' if matches!(self.input.peek(), Some('"' | '#')) => self.raw_string()?,
+
+            // -----------------------------------------------------------------------------
+            //   - Single char tokens -
+            // -----------------------------------------------------------------------------
+            '\n' => self.single_char_token(Token::Newline),
+            '=' => self.single_char_token(Token::Equal),
+            '@' => self.single_char_token(Token::At),
+            '!' => self.single_char_token(Token::Bang),
+            '{' => self.single_char_token(Token::LeftBrace),
+            '}' => self.single_char_token(Token::RightBrace),
+
+            '-' | '0'..='9' => self.int(c)?,
+            // A `+` on its own is whitespace
+            '+' if self.input.peek().is_some_and(char::is_ascii_digit) => self.int(c)?,
+            'a'..='z' | 'A'..='Z' => self.ident(c)?,
+            '"' | '\'' => self.string(c)?,
+            _ => self.whitespace(),
+        }
+        Ok(())
+    }
+
+    // Skip the rest of the line after an error, up to the newline
+    fn skip_line(&mut self) {
+        while let Some(&c) = self.input.peek()
+            && c != '\n'
+        {
+            self.consume_char();
+        }
+        self.current_span = self.next_span;
     }
 
     fn string(&mut self, quote: char) -> Result<()> {
         let mut buffer = String::new();
-        let mut escaping = false;
 
         loop {
-            match self.input.peek() {
-                Some('\\') if !escaping => {
-                    if let Some('"' | '\\') = self.input.peek() {
-                        escaping = true;
-                    } else {
-                        escaping = false;
-                    }
-                }
-                Some('n') if escaping => {
-                    buffer.push('\n');
-                    escaping = false;
-                }
-                Some(c @ '\\') if escaping => {
-                    buffer.push(*c);
-                    escaping = false;
-                }
-                Some(c) if escaping && *c == quote => {
-                    buffer.push(*c);
-                    escaping = false;
-                }
+            let Some(c) = self.input.peek().copied() else {
+                return Error::unterminated_string(self.next_span, self.source);
+            };
+            self.consume_char();
+
+            match c {
                 // Closing quote
-                Some(c) if *c == quote => {
-                    self.consume_char();
-                    break;
-                }
-                Some(c) => buffer.push(*c),
-                None => return Error::unterminated_string(self.next_span, self.source),
+                c if c == quote => break,
+                '\\' => self.escape(&mut buffer)?,
+                c => buffer.push(c),
             }
+        }
+
+        self.push_token(Token::Str(buffer));
+        Ok(())
+    }
+
+    // The backslash is already consumed
+    fn escape(&mut self, buffer: &mut String) -> Result<()> {
+        let Some(c) = self.input.peek().copied() else {
+            return Error::unterminated_string(self.next_span, self.source);
+        };
+        self.consume_char();
+
+        let c = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' | '"' | '\'' => c,
+            'u' => self.unicode_escape()?,
+            // Unknown escapes are kept as they are
+            c => {
+                buffer.push('\\');
+                c
+            }
+        };
+
+        buffer.push(c);
+        Ok(())
+    }
 
+    // \u{1F99C}, the `\u` is already consumed
+    fn unicode_escape(&mut self) -> Result<char> {
+        let span = self.next_span;
+
+        if self.input.peek() != Some(&'{') {
+            return Error::invalid_escape(span, self.source);
+        }
+        self.consume_char();
+
+        let mut hex = String::new();
+        while let Some(c) = self.input.peek().copied().filter(char::is_ascii_hexdigit) {
+            hex.push(c);
+            self.consume_char();
+        }
+
+        if self.input.peek() != Some(&'}') || hex.is_empty() || hex.len() > 6 {
+            return Error::invalid_escape(span, self.source);
+        }
+        self.consume_char();
+
+        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+            Some(c) => Ok(c),
+            None => Error::invalid_escape(span, self.source),
+        }
+    }
+
+    // r"..." or r#"..."#, with any number of #, and no escapes.
+    // The `r` is already consumed.
+    fn raw_string(&mut self) -> Result<()> {
+        let mut hashes = 0;
+        while self.input.peek() == Some(&'#') {
+            hashes += 1;
+            self.consume_char();
+        }
+
+        if self.input.peek() != Some(&'"') {
+            return Error::unterminated_string(self.next_span, self.source);
+        }
+        self.consume_char();
+
+        let mut buffer = String::new();
+        loop {
+            let Some(c) = self.input.peek().copied() else {
+                return Error::unterminated_string(self.next_span, self.source);
+            };
             self.consume_char();
+
+            if c != '"' {
+                buffer.push(c);
+                continue;
+            }
+
+            // A quote only closes the string if it's followed by the same number of #
+            let mut closing = 0;
+            while closing < hashes && self.input.peek() == Some(&'#') {
+                closing += 1;
+                self.consume_char();
+            }
+
+            if closing == hashes {
+                break;
+            }
+
+            buffer.push('"');
+            buffer.extend(std::iter::repeat_n('#', closing));
         }
 
         self.push_token(Token::Str(buffer));
         Ok(())
     }
 
-    fn ident(&mut self, initial: char) -> Result<()> {
-        let mut buffer = String::from(initial);
+    // <<NAME followed by lines up to a line with only NAME on it.
+    // The common indentation of the lines is removed.
+    // The first `<` is already consumed.
+    fn heredoc(&mut self) -> Result<()> {
+        self.consume_char();
+        let span = self.next_span;
+
+        let mut delimiter = String::new();
+        while let Some(c @ ('a'..='z' | 'A'..='Z' | '0'..='9' | '_')) = self.input.peek().copied() {
+            delimiter.push(c);
+            self.consume_char();
+        }
+
+        // Nothing but whitespace is allowed after the delimiter
+        while let Some(c) = self.input.peek().copied()
+            && c != '\n'
+        {
+            if !c.is_whitespace() {
+                return Error::invalid_heredoc(span, self.source);
+            }
+            self.consume_char();
+        }
+
+        if delimiter.is_empty() || self.input.peek().is_none() {
+            return Error::invalid_heredoc(span, self.source);
+        }
+        self.consume_char();
 
+        let mut lines = vec![];
         loop {
-            match self.input.peek() {
-                Some(c @ ('a'..='z' | 'A'..='Z' | '0'..'9' | '_' | '-')) => {
-                    buffer.push(*c);
-                    self.consume_char();
-                }
-                Some(_) | None => break,
+            // The rest of the line after the delimiter, like `nonl`, is left for the parser
+            if self.heredoc_end(&delimiter) {
+                break;
+            }
+
+            let mut line = String::new();
+            while let Some(c) = self.input.peek().copied()
+                && c != '\n'
+            {
+                line.push(c);
+                self.consume_char();
+            }
+
+            if self.input.peek().is_none() {
+                return Error::unterminated_string(span, self.source);
             }
+            self.consume_char();
+
+            lines.push(line);
+        }
+
+        self.push_token(Token::Str(dedent(&lines)));
+        Ok(())
+    }
+
+    // Consume the delimiter if it starts the next line, after any indentation
+    fn heredoc_end(&mut self, delimiter: &str) -> bool {
+        let line = self.input.clone().take_while(|c| *c != '\n').collect::<String>();
+        let indent = line.len() - line.trim_start().len();
+        let Some(rest) = line[indent..].strip_prefix(delimiter) else { return false };
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return false;
+        }
+
+        for _ in line[..indent + delimiter.len()].chars() {
+            self.consume_char();
+        }
+        true
+    }
+
+    fn ident(&mut self, initial: char) -> Result<()> {
+        let mut buffer = String::from(initial);
+
+        while let Some(c @ ('a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-')) = self.input.peek() {
+            buffer.push(*c);
+            self.consume_char();
         }
 
         let token = match buffer.as_str() {
             "as" => Token::As,
             "audio" => Token::Audio,
+            "call" => Token::Call,
+            "chapter" => Token::Chapter,
             "clear" => Token::Clear,
+            "define" => Token::Define,
             "delete" => Token::Delete,
             "extension" => Token::SetExtension,
             "false" => Token::Bool(false),
             "find" => Token::Find,
             "goto" => Token::Goto,
+            "include" => Token::Include,
             "insert" => Token::Insert,
             "jitter" => Token::Jitter,
+            "let" => Token::Let,
             "linepause" => Token::LinePause,
             "load" => Token::Load,
             "nonl" => Token::NoNewline,
             "numbers" => Token::ShowLineNumbers,
+            "onfinish" => Token::OnFinish,
+            "pause" | "waitkey" => Token::Pause,
             "popup" => Token::Popup,
             "closepopup" => Token::ClosePopup,
+            "repeat" => Token::Repeat,
             "replace" => Token::Replace,
+            "seed" => Token::Seed,
             "select" => Token::Select,
             "speed" => Token::Speed,
             "title" => Token::SetTitle,
@@ -179,28 +363,65 @@ impl<'src> Lexer<'src> {
         Ok(())
     }
 
+    // An int, a float (1.5) or a duration (500ms, 1.5s, 2m)
     fn int(&mut self, c: char) -> Result<()> {
+        let span = self.next_span;
         let mut buffer = String::from(c);
-        loop {
-            match self.input.peek() {
-                Some(c @ '0'..='9') => {
-                    buffer.push(*c);
-                    self.consume_char();
-                }
-                Some(_) | None => break,
-            }
+        self.digits(&mut buffer);
+
+        // Only a `.` followed by a digit makes a float
+        let mut float = false;
+        if self.input.peek() == Some(&'.') && self.input.clone().nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            float = true;
+            buffer.push('.');
+            self.consume_char();
+            self.digits(&mut buffer);
         }
 
-        let int = match buffer.parse() {
-            Ok(int) => int,
-            Err(_) => return Error::invalid_int(self.next_span, self.source),
+        let mut unit = String::new();
+        while let Some(c @ ('a'..='z' | 'A'..='Z')) = self.input.peek().copied() {
+            unit.push(c);
+            self.consume_char();
+        }
+
+        let token = match unit.as_str() {
+            "" if float => match buffer.parse() {
+                Ok(float) => Token::Float(float),
+                Err(_) => return Error::invalid_int(span, self.source),
+            },
+            "" => match buffer.parse() {
+                Ok(int) => Token::Int(int),
+                Err(_) => return Error::invalid_int(span, self.source),
+            },
+            unit => {
+                let scale = match unit {
+                    "ms" => 0.001,
+                    "s" => 1.0,
+                    "m" => 60.0,
+                    _ => return Error::invalid_duration(span, self.source),
+                };
+                let duration = buffer
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|value| Duration::try_from_secs_f64(value * scale).ok());
+                match duration {
+                    Some(duration) => Token::Duration(duration),
+                    None => return Error::invalid_duration(span, self.source),
+                }
+            }
         };
 
-        let token = Token::Int(int);
         self.push_token(token);
         Ok(())
     }
 
+    fn digits(&mut self, buffer: &mut String) {
+        while let Some(c @ '0'..='9') = self.input.peek().copied() {
+            buffer.push(c);
+            self.consume_char();
+        }
+    }
+
     fn push_token(&mut self, token: Token) {
         self.current_span.token = self.tokens.len() as u32;
         self.spans.push(self.current_span);
@@ -212,30 +433,47 @@ impl<'src> Lexer<'src> {
         // Consume the last '\'
         self.consume_char();
 
-        while let Some(&c) = self.input.peek() {
+        // The newline is left as its own token, so the comment doesn't end up
+        // hiding the end of the line
+        while let Some(&c) = self.input.peek()
+            && c != '\n'
+        {
             self.consume_char();
-            if c == '\n' {
-                break;
-            }
         }
 
         self.push_token(Token::Comment);
     }
 
     fn whitespace(&mut self) {
-        loop {
-            match self.input.peek() {
-                Some(c) if c.is_ascii_whitespace() => {
-                    self.consume_char();
-                    break;
-                }
-                Some(_) | None => break,
-            }
+        // A newline is never part of the whitespace, so the parser can find the end of a line
+        if let Some(c) = self.input.peek()
+            && c.is_ascii_whitespace()
+            && *c != '\n'
+        {
+            self.consume_char();
         }
         self.push_token(Token::Whitespace);
     }
 }
 
+// Join the lines, without the indentation they all have in common.
+// Blank lines don't count towards the indentation.
+fn dedent(lines: &[String]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for line in lines {
+        output.push_str(line.get(indent..).unwrap_or_default());
+        output.push('\n');
+    }
+    output
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -288,6 +526,17 @@ mod test {
 
         let expected = vec![goto(), whitespace(), int(1), whitespace(), int(2), eof()];
         assert_eq!(tokens, expected);
+
+        let tokens = lex_tokens("goto +1 -2");
+        let expected = vec![goto(), whitespace(), int(1), whitespace(), int(-2), eof()];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn lex_every_digit() {
+        let tokens = lex_tokens("goto 9 0");
+        let expected = vec![goto(), whitespace(), int(9), whitespace(), int(0), eof()];
+        assert_eq!(tokens, expected);
     }
 
     #[test]
@@ -299,6 +548,89 @@ mod test {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn lex_escapes() {
+        let input = r#""a\tb\\c\"d\u{1F99C}\q""#;
+        let tokens = lex_tokens(input);
+
+        let expected = vec![string("a\tb\\c\"d🦜\\q"), eof()];
+        assert_eq!(tokens, expected);
+
+        assert!(lex(r#""\u{110000}""#).is_err());
+        assert!(lex(r#""\u1234""#).is_err());
+    }
+
+    #[test]
+    fn lex_raw_string() {
+        let input = r###"r"a\n" r##"say "hi"# "##"###;
+        let tokens = lex_tokens(input);
+
+        let expected = vec![string("a\\n"), whitespace(), string("say \"hi\"# "), eof()];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn lex_heredoc() {
+        let input = "type <<END\n        fn main() {\n\n            println!(\"\\n\");\n        }\n    END\nwait 1";
+        let tokens = lex_tokens(input);
+
+        let expected = vec![
+            Token::Type,
+            whitespace(),
+            string("fn main() {\n\n    println!(\"\\n\");\n}\n"),
+            nl(),
+            Token::Wait,
+            whitespace(),
+            int(1),
+            eof(),
+        ];
+        assert_eq!(tokens, expected);
+
+        // Anything after the delimiter is lexed as usual
+        let tokens = lex_tokens("type <<END\n  a\n  END nonl\n");
+        let expected = vec![
+            Token::Type,
+            whitespace(),
+            string("a\n"),
+            whitespace(),
+            Token::NoNewline,
+            nl(),
+            eof(),
+        ];
+        assert_eq!(tokens, expected);
+
+        // Only the whole delimiter ends the heredoc
+        let tokens = lex_tokens("type <<END\nENDING\nEND");
+        assert_eq!(tokens[2], string("ENDING\n"));
+
+        assert!(lex("type <<END\nhello\n").is_err());
+        assert!(lex("type <<END oops\nhello\nEND").is_err());
+    }
+
+    #[test]
+    fn lex_numbers_and_durations() {
+        let input = "1.5 500ms 1.5s 2m 1.";
+        let tokens = lex_tokens(input);
+
+        let expected = vec![
+            Token::Float(1.5),
+            whitespace(),
+            Token::Duration(Duration::from_millis(500)),
+            whitespace(),
+            Token::Duration(Duration::from_millis(1500)),
+            whitespace(),
+            Token::Duration(Duration::from_secs(120)),
+            whitespace(),
+            int(1),
+            whitespace(),
+            eof(),
+        ];
+        assert_eq!(tokens, expected);
+
+        assert!(lex("5h").is_err());
+        assert!(lex("-5s").is_err());
+    }
+
     #[test]
     fn lex_negative_int() {
         let input = "-123";
@@ -335,7 +667,8 @@ mod test {
             Span {
                 token: 0,
                 line: 1,
-                col: 1
+                col: 1,
+                offset: 0,
             },
             start
         );
@@ -343,7 +676,8 @@ mod test {
             Span {
                 token: 1,
                 line: 1,
-                col: 10,
+                col: 11,
+                offset: 10,
             },
             end
         );
@@ -1,12 +1,49 @@
-pub use instruction::{Dest, Instruction, Instructions, Source};
+pub use builder::Script;
+pub use error::{Error, Errors, snippet};
+pub use format::format;
+pub use help::{Keyword, keyword, keywords};
+pub use instruction::{Anchor, Dest, Instruction, Instructions, OnFinish, Source, Spanned};
+pub use token::Span;
 
+mod builder;
 mod error;
+mod format;
+mod help;
 mod instruction;
 mod lexer;
 mod parse;
 mod token;
 
-pub fn parse<'a>(input: &'a str) -> error::Result<Instructions> {
-    let tokens = lexer::lex(input)?;
-    parse::parse(tokens)
+/// Parse a script, returning every error in it if there are any.
+pub fn parse(input: &str) -> Result<Instructions, Errors> {
+    let (tokens, mut errors) = lexer::lex_all(input);
+    let parse_errors = match parse::parse(tokens) {
+        Ok(instructions) if errors.is_empty() => return Ok(instructions),
+        Ok(_) => vec![],
+        Err(parse_errors) => parse_errors.into_inner(),
+    };
+
+    // The rest of a line with a lex error was skipped, so the parser
+    // would only complain about what's missing from it
+    let lines = errors.iter().map(|error| error.span().0.line).collect::<Vec<_>>();
+    errors.extend(
+        parse_errors
+            .into_iter()
+            .filter(|error| !lines.contains(&error.span().0.line)),
+    );
+    errors.sort_by_key(|error| error.span().0.offset);
+    Err(Errors::new(errors))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_lex_and_parse_errors() {
+        let errors = parse("wait 1q\ntyp \"a\"\nwait 2zz").unwrap_err();
+        let lines = errors.iter().map(|error| error.span().0.line).collect::<Vec<_>>();
+        assert_eq!(lines, [1, 2, 3]);
+        assert!(errors.iter().nth(1).unwrap().message().contains("did you mean `type`?"));
+    }
 }
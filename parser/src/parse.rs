@@ -1,17 +1,41 @@
-use crate::error::{Error, Result};
-use crate::instruction::{Dest, Instruction, Instructions, Source};
+use std::time::Duration;
+
+use crate::error::{Error, Errors, Result};
+use crate::help;
+use crate::instruction::{Anchor, Dest, Instruction, Instructions, OnFinish, Source, Spanned};
 use crate::token::{Token, Tokens};
 
 struct Parser<'src> {
     tokens: Tokens<'src>,
+    errors: Vec<Error>,
 }
 
 impl<'src> Parser<'src> {
     fn new(tokens: Tokens<'src>) -> Self {
-        Self { tokens }
+        Self { tokens, errors: vec![] }
     }
 
-    fn parse(&mut self) -> Result<Instructions> {
+    fn parse(mut self) -> std::result::Result<Instructions, Errors> {
+        let instructions = self.instructions(Token::Eof);
+
+        match instructions {
+            Ok(instructions) if self.errors.is_empty() => Ok(Instructions::new(instructions, self.tokens.source)),
+            Ok(_) => Err(Errors::new(self.errors)),
+            Err(error) => {
+                self.errors.push(error);
+                Err(Errors::new(self.errors))
+            }
+        }
+    }
+
+    // Parse instructions, one per line, until the `end` token.
+    // An invalid line is recorded as an error and skipped.
+    fn instructions(&mut self, end: Token) -> Result<Vec<Spanned>> {
+        let expected = match end {
+            Token::Eof => "newline or end of file",
+            _ => "newline or }",
+        };
+
         let mut instructions = vec![];
 
         loop {
@@ -20,35 +44,69 @@ impl<'src> Parser<'src> {
                     self.tokens.consume();
                     continue;
                 }
-                Token::Eof => break,
+                token if *token == end => {
+                    self.tokens.take();
+                    break;
+                }
+                Token::Eof => {
+                    let token = self.tokens.take();
+                    return Error::unexpected_token(expected, token, self.tokens.spans(), self.tokens.source);
+                }
                 _ => (),
             }
 
-            let inst = self.next_instruction()?;
-            instructions.push(inst);
-
-            match self.tokens.take() {
-                Token::Newline | Token::Comment | Token::Whitespace => continue,
-                Token::Eof => break,
-                token => {
-                    return Error::unexpected_token(
-                        "newline or end of file",
-                        token,
-                        self.tokens.spans(),
-                        self.tokens.source,
-                    );
+            let start = self.tokens.span();
+            let res = self.next_instruction().and_then(|instruction| {
+                instructions.push(Spanned {
+                    instruction,
+                    start,
+                    end: self.tokens.spans().1,
+                });
+                self.end_of_line(&end, expected)
+            });
+
+            match res {
+                Ok(true) => break,
+                Ok(false) => continue,
+                Err(error) => {
+                    self.errors.push(error);
+                    self.tokens.skip_line();
                 }
             }
+        }
 
-            // there has to be either newline OR eof here
+        Ok(instructions)
+    }
+
+    // There has to be either newline OR the end token after an instruction.
+    // Returns true if it was the end token.
+    fn end_of_line(&mut self, end: &Token, expected: &'static str) -> Result<bool> {
+        // Inside a block, instructions can follow each other on one line
+        if *end == Token::RightBrace && help::syntax(self.tokens.current()).is_some() {
+            return Ok(false);
         }
 
-        Ok(Instructions::new(instructions))
+        match self.tokens.take() {
+            Token::Newline | Token::Comment | Token::Whitespace => Ok(false),
+            token if token == *end => Ok(true),
+            token => Error::unexpected_token(expected, token, self.tokens.spans(), self.tokens.source),
+        }
     }
 
     fn next_instruction(&mut self) -> Result<Instruction> {
-        match self.tokens.take() {
+        let token = self.tokens.take();
+        let help = help::syntax(&token);
+        self.instruction(token).map_err(|e| e.with_help(help))
+    }
+
+    fn instruction(&mut self, token: Token) -> Result<Instruction> {
+        match token {
             Token::Load => self.load(),
+            Token::Include => self.include(),
+            Token::Let => self.binding(),
+            Token::Define => self.define(),
+            Token::Call => self.call(),
+            Token::Repeat => self.repeat(),
             Token::Goto => self.goto(),
             Token::Type => self.print(false),
             Token::TypeNl => self.print(true),
@@ -56,6 +114,7 @@ impl<'src> Parser<'src> {
             Token::Replace => self.change(),
             Token::Delete => self.delete(),
             Token::Speed => self.speed(),
+            Token::Seed => self.seed(),
             Token::Select => self.select(),
             Token::Find => self.find(),
             Token::LinePause => self.linepause(),
@@ -69,7 +128,20 @@ impl<'src> Parser<'src> {
             Token::Popup => self.popup(),
             Token::ClosePopup => self.closepopup(),
             Token::Wait => self.wait(),
-            token => Error::invalid_instruction(token, self.tokens.spans(), self.tokens.source),
+            Token::Pause => self.pause(),
+            Token::Chapter => self.chapter(),
+            Token::OnFinish => self.on_finish(),
+            Token::Ident(word) => match help::suggest(&word) {
+                Some((keyword, token)) => Error::invalid_instruction(
+                    Token::Ident(word),
+                    Some(keyword),
+                    self.tokens.spans(),
+                    self.tokens.source,
+                )
+                .map_err(|e| e.with_help(help::syntax(token))),
+                None => Error::invalid_instruction(Token::Ident(word), None, self.tokens.spans(), self.tokens.source),
+            },
+            token => Error::invalid_instruction(token, None, self.tokens.spans(), self.tokens.source),
         }
     }
 
@@ -78,30 +150,141 @@ impl<'src> Parser<'src> {
             Token::Str(path) => match self.tokens.take() {
                 Token::As => match self.tokens.take() {
                     Token::Ident(key) => Ok(Instruction::Load(path.into(), key)),
-                    token => return Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
+                    token => Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
                 },
-                token => return Error::invalid_arg("as", token, self.tokens.spans(), self.tokens.source),
+                token => Error::invalid_arg("as", token, self.tokens.spans(), self.tokens.source),
             },
             token => Error::invalid_arg("string", token, self.tokens.spans(), self.tokens.source),
         }
     }
 
+    fn include(&mut self) -> Result<Instruction> {
+        // include <string>
+        match self.tokens.take() {
+            Token::Str(path) => Ok(Instruction::Include(path.into())),
+            token => Error::invalid_arg("string", token, self.tokens.spans(), self.tokens.source),
+        }
+    }
+
+    fn binding(&mut self) -> Result<Instruction> {
+        // let <ident> = <string>
+        let name = match self.tokens.take() {
+            Token::Ident(name) => name,
+            token => return Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        match self.tokens.take() {
+            Token::Equal => {}
+            token => return Error::invalid_arg("=", token, self.tokens.spans(), self.tokens.source),
+        }
+
+        let value = match self.tokens.take() {
+            Token::Str(value) => value,
+            token => return Error::invalid_arg("string", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        Ok(Instruction::Let { name, value })
+    }
+
+    fn define(&mut self) -> Result<Instruction> {
+        // define <ident> [<ident>...] { <instructions> }
+        let name = match self.tokens.take() {
+            Token::Ident(name) => name,
+            token => return Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        let mut params = vec![];
+        loop {
+            match self.tokens.take() {
+                Token::Ident(param) => params.push(param),
+                Token::LeftBrace => break,
+                token => return Error::invalid_arg("ident or {", token, self.tokens.spans(), self.tokens.source),
+            }
+        }
+
+        let body = self.instructions(Token::RightBrace)?;
+        Ok(Instruction::Define { name, params, body })
+    }
+
+    fn call(&mut self) -> Result<Instruction> {
+        // call <ident> [<string>...]
+        let name = match self.tokens.take() {
+            Token::Ident(name) => name,
+            token => return Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        let mut args = vec![];
+        while let Some(Token::Str(arg)) = self.tokens.take_if(|token| matches!(token, Token::Str(_))) {
+            args.push(arg);
+        }
+
+        Ok(Instruction::Call { name, args })
+    }
+
+    fn repeat(&mut self) -> Result<Instruction> {
+        // repeat <int> { <instructions> }
+        let count = match self.tokens.take() {
+            Token::Int(count) if count >= 0 => count as u64,
+            token => return Error::invalid_arg("non-negative int", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        match self.tokens.take() {
+            Token::LeftBrace => {}
+            token => return Error::invalid_arg("{", token, self.tokens.spans(), self.tokens.source),
+        }
+
+        let body = self.instructions(Token::RightBrace)?;
+        Ok(Instruction::Repeat { count, body })
+    }
+
     fn goto(&mut self) -> Result<Instruction> {
-        // goto <ident>|<int> <int>
-        // <ident>
-        let instr = match self.tokens.take() {
-            Token::Ident(ident) => Instruction::Goto(Dest::Marker(ident)),
-            Token::Int(row) => match self.tokens.take() {
-                Token::Int(col) => Instruction::Goto(Dest::Relative {
-                    row: row as i32,
-                    col: col as i32,
-                }),
-                token => return Error::invalid_arg("number", token, self.tokens.spans(), self.tokens.source),
+        // goto <int> <int>
+        // goto line <int> [col <int>]
+        // goto end|bol|eol|indent
+        // goto <ident> [<int> <int>]
+        let dest = match self.tokens.take() {
+            Token::Int(row) => Dest::Relative {
+                row: saturate(row),
+                col: self.number()?,
+            },
+            Token::Ident(ident) if ident == "line" => {
+                let line = self.line_number()?;
+                let col = match self.tokens.consume_if(Token::Ident("col".into())) {
+                    true => self.line_number()?,
+                    false => 1,
+                };
+                Dest::Absolute { line, col }
+            }
+            Token::Ident(ident) => match Anchor::from_name(&ident) {
+                Some(anchor) => Dest::Anchor(anchor),
+                None => match self.tokens.take_if(|token| matches!(token, Token::Int(_))) {
+                    Some(Token::Int(row)) => Dest::MarkerOffset {
+                        name: ident,
+                        row: saturate(row),
+                        col: self.number()?,
+                    },
+                    _ => Dest::Marker(ident),
+                },
             },
             token => return Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
         };
 
-        Ok(instr)
+        Ok(Instruction::Goto(dest))
+    }
+
+    fn number(&mut self) -> Result<i32> {
+        match self.tokens.take() {
+            Token::Int(number) => Ok(saturate(number)),
+            token => Error::invalid_arg("number", token, self.tokens.spans(), self.tokens.source),
+        }
+    }
+
+    // Lines and columns count from one
+    fn line_number(&mut self) -> Result<u32> {
+        match self.tokens.take() {
+            Token::Int(number) if number >= 1 => Ok(u32::try_from(number).unwrap_or(u32::MAX)),
+            token => Error::invalid_arg("number above zero", token, self.tokens.spans(), self.tokens.source),
+        }
     }
 
     fn print(&mut self, prefix_newline: bool) -> Result<Instruction> {
@@ -121,9 +304,9 @@ impl<'src> Parser<'src> {
 
     fn insert(&mut self) -> Result<Instruction> {
         match self.tokens.take() {
-            Token::Str(s) => return Ok(Instruction::Insert(Source::Str(s))),
-            Token::Ident(ident) => return Ok(Instruction::Insert(Source::Ident(ident))),
-            token => return Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
+            Token::Str(s) => Ok(Instruction::Insert(Source::Str(s))),
+            Token::Ident(ident) => Ok(Instruction::Insert(Source::Ident(ident))),
+            token => Error::invalid_arg("ident", token, self.tokens.spans(), self.tokens.source),
         }
     }
 
@@ -151,8 +334,13 @@ impl<'src> Parser<'src> {
 
     fn speed(&mut self) -> Result<Instruction> {
         // <int>
+        let speed = self.duration(Duration::from_millis(1))?;
+        Ok(Instruction::Speed(speed))
+    }
+
+    fn seed(&mut self) -> Result<Instruction> {
         let instr = match self.tokens.take() {
-            Token::Int(speed) => Instruction::Speed(speed as u64),
+            Token::Int(seed) => Instruction::Seed(seed as u64),
             token => return Error::invalid_arg("int", token, self.tokens.spans(), self.tokens.source),
         };
 
@@ -184,12 +372,8 @@ impl<'src> Parser<'src> {
     }
 
     fn linepause(&mut self) -> Result<Instruction> {
-        let instr = match self.tokens.take() {
-            Token::Int(ms) => Instruction::LinePause(ms as u64),
-            token => return Error::invalid_arg("int", token, self.tokens.spans(), self.tokens.source),
-        };
-
-        Ok(instr)
+        let line_pause = self.duration(Duration::from_millis(1))?;
+        Ok(Instruction::LinePause(line_pause))
     }
 
     fn set_extension(&mut self) -> Result<Instruction> {
@@ -224,12 +408,8 @@ impl<'src> Parser<'src> {
     }
 
     fn jitter(&mut self) -> Result<Instruction> {
-        let instr = match self.tokens.take() {
-            Token::Int(jitter) => Instruction::Jitter(jitter as u64),
-            token => return Error::invalid_arg("boolean", token, self.tokens.spans(), self.tokens.source),
-        };
-
-        Ok(instr)
+        let jitter = self.duration(Duration::from_millis(1))?;
+        Ok(Instruction::Jitter(jitter))
     }
 
     fn theme(&mut self) -> Result<Instruction> {
@@ -264,16 +444,71 @@ impl<'src> Parser<'src> {
     }
 
     fn wait(&mut self) -> Result<Instruction> {
+        let wait = self.duration(Duration::from_secs(1))?;
+        Ok(Instruction::Wait(wait))
+    }
+
+    // A duration with a unit, or a bare number in the given `unit`
+    fn duration(&mut self, unit: Duration) -> Result<Duration> {
+        let duration = match self.tokens.take() {
+            Token::Duration(duration) => duration,
+            Token::Int(int) if int >= 0 => unit.saturating_mul(u32::try_from(int).unwrap_or(u32::MAX)),
+            Token::Float(float) if float >= 0.0 => match Duration::try_from_secs_f64(unit.as_secs_f64() * float) {
+                Ok(duration) => duration,
+                Err(_) => {
+                    return Error::invalid_arg(
+                        "duration",
+                        Token::Float(float),
+                        self.tokens.spans(),
+                        self.tokens.source,
+                    );
+                }
+            },
+            token => return Error::invalid_arg("duration", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        Ok(duration)
+    }
+
+    fn pause(&mut self) -> Result<Instruction> {
+        // pause [<string>]
+        let hint = match self.tokens.take_if(|token| matches!(token, Token::Str(_))) {
+            Some(Token::Str(hint)) => Some(hint),
+            _ => None,
+        };
+
+        Ok(Instruction::Pause(hint))
+    }
+
+    fn on_finish(&mut self) -> Result<Instruction> {
+        // onfinish hold|loop|exit
+        let on_finish = match self.tokens.take() {
+            Token::Ident(ident) if ident == "hold" => OnFinish::Hold,
+            Token::Ident(ident) if ident == "loop" => OnFinish::Loop,
+            Token::Ident(ident) if ident == "exit" => OnFinish::Exit,
+            token => return Error::invalid_arg("hold, loop or exit", token, self.tokens.spans(), self.tokens.source),
+        };
+
+        Ok(Instruction::OnFinish(on_finish))
+    }
+
+    fn chapter(&mut self) -> Result<Instruction> {
+        // chapter <ident>|<string>
         let instr = match self.tokens.take() {
-            Token::Int(seconds) => Instruction::Wait(seconds as u64),
-            token => return Error::invalid_arg("seconds", token, self.tokens.spans(), self.tokens.source),
+            Token::Ident(name) | Token::Str(name) => Instruction::Chapter(name),
+            token => return Error::invalid_arg("ident or string", token, self.tokens.spans(), self.tokens.source),
         };
 
         Ok(instr)
     }
 }
 
-pub fn parse(tokens: Tokens<'_>) -> Result<Instructions> {
+// Rows and columns past what fits are as far as they can go
+fn saturate(number: i64) -> i32 {
+    number.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+pub fn parse(tokens: Tokens<'_>) -> std::result::Result<Instructions, Errors> {
     Parser::new(tokens).parse()
 }
 
@@ -283,10 +518,24 @@ mod test {
 
     use super::*;
     use crate::lexer::lex;
+    use crate::token::Span;
 
-    fn parse(input: &str) -> Result<Vec<Instruction>> {
+    // The spans are reset so the instructions can be compared,
+    // see `parse_spans` for the spans themselves
+    fn parse(input: &str) -> std::result::Result<Vec<Instruction>, Errors> {
         let tokens = lex(input)?;
-        super::parse(tokens).map(|i| i.take_instructions())
+        let instructions = super::parse(tokens)?.take_instructions();
+        Ok(instructions.into_iter().map(Spanned::unspan).collect())
+    }
+
+    fn block(body: Vec<Instruction>) -> Vec<Spanned> {
+        body.into_iter()
+            .map(|instruction| Spanned {
+                instruction,
+                start: Span::INITIAL,
+                end: Span::INITIAL,
+            })
+            .collect()
     }
 
     fn parse_ok(input: &str) -> Vec<Instruction> {
@@ -339,7 +588,101 @@ mod test {
     }
 
     fn wait(secs: u64) -> Instruction {
-        Instruction::Wait(secs)
+        Instruction::Wait(Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn parse_durations() {
+        let output = parse_ok("wait 1.5\nwait 500ms\nspeed 20\nspeed 0.1s\nlinepause 1s\njitter 12.5");
+        let expected = vec![
+            Instruction::Wait(Duration::from_millis(1500)),
+            Instruction::Wait(Duration::from_millis(500)),
+            Instruction::Speed(Duration::from_millis(20)),
+            Instruction::Speed(Duration::from_millis(100)),
+            Instruction::LinePause(Duration::from_secs(1)),
+            Instruction::Jitter(Duration::from_micros(12500)),
+        ];
+        assert_eq!(output, expected);
+        assert!(parse("wait -1").is_err());
+    }
+
+    #[test]
+    fn recover_from_errors() {
+        let errors = parse("wait\nclear\nbogus 1 2\nspeed x // comment\ntype \"ok\" nope\nclear").unwrap_err();
+        assert_eq!(errors.len(), 4);
+
+        let output = errors.to_string();
+        assert!(output.contains("expected `duration`, found `<nl>`"));
+        assert!(output.contains("invalid instruction: `bogus`"));
+        assert!(output.contains("expected `duration`, found `x`"));
+        assert!(output.contains("found `nope`"));
+    }
+
+    #[test]
+    fn suggest_keyword_with_help() {
+        let output = parse("typnl \"hello\"").unwrap_err().to_string();
+        assert!(output.contains("invalid instruction: `typnl`, did you mean `typenl`?"));
+        assert!(output.contains("help: typenl <ident>|<string> [nonl]"));
+    }
+
+    #[test]
+    fn underline_whole_token() {
+        let output = parse("wait \"long string\"").unwrap_err().to_string();
+        let expected = "\n1: wait \"long string\"\n        ^^^^^^^^^^^^^ expected `duration`, found `\"long string\"`\n   help: wait <seconds>|<duration>\n";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_spans() {
+        let tokens = lex("clear\n  wait 2s // comment\nrepeat 2 {\n    delete\n}").unwrap();
+        let instructions = super::parse(tokens).unwrap().take_instructions();
+        let ranges = |spanned: &Spanned| (spanned.start.line, spanned.start.col, spanned.end.line, spanned.end.col);
+
+        assert_eq!(ranges(&instructions[0]), (1, 1, 1, 6));
+        assert_eq!(ranges(&instructions[1]), (2, 3, 2, 10));
+        assert_eq!(ranges(&instructions[2]), (3, 1, 5, 2));
+
+        let Instruction::Repeat { body, .. } = &instructions[2].instruction else { panic!() };
+        assert_eq!(ranges(&body[0]), (4, 5, 4, 11));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let tokens = lex("load \"foo.rs\" as foo\nrepeat 2 {\n    goto 1, 2\n}\ntype foo").unwrap();
+        let instructions = super::parse(tokens).unwrap();
+        let json = serde_json::to_string(&instructions).unwrap();
+        let output: Instructions = serde_json::from_str(&json).unwrap();
+        assert_eq!(output.source(), instructions.source());
+        assert_eq!(output.take_instructions(), instructions.take_instructions());
+
+        // The spans can be left out
+        let json = r#"{"instructions": [{"instruction": {"Goto": {"Marker": "a"}}}]}"#;
+        let output: Instructions = serde_json::from_str(json).unwrap();
+        let output = output
+            .take_instructions()
+            .into_iter()
+            .map(Spanned::unspan)
+            .collect::<Vec<_>>();
+        assert_eq!(output, vec![goto("a")]);
+    }
+
+    #[test]
+    fn parse_block_on_one_line() {
+        let output = parse_ok(r#"define save { popup "saved" wait 1 closepopup }"#);
+        let expected = vec![Instruction::Define {
+            name: "save".into(),
+            params: vec![],
+            body: block(vec![
+                Instruction::Popup("saved".into()),
+                Instruction::Wait(Duration::from_secs(1)),
+                Instruction::ClosePopup,
+            ]),
+        }];
+        assert_eq!(output, expected);
+
+        // But not outside of a block
+        assert!(parse("clear wait 1").is_err());
     }
 
     #[test]
@@ -360,6 +703,51 @@ mod test {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn parse_goto_absolute() {
+        let output = parse_ok("goto line 12 col 4\ngoto line 3\ngoto line 4294967297");
+        let expected = vec![
+            goto(Dest::Absolute { line: 12, col: 4 }),
+            goto(Dest::Absolute { line: 3, col: 1 }),
+            goto(Dest::Absolute { line: u32::MAX, col: 1 }),
+        ];
+        assert_eq!(output, expected);
+
+        // `line` is never a marker
+        assert!(parse("goto line").is_err());
+        assert!(parse("goto line 0").is_err());
+        assert!(parse("goto line 1 col -2").is_err());
+    }
+
+    #[test]
+    fn parse_goto_anchor() {
+        let output = parse_ok("goto end\ngoto bol\ngoto eol\ngoto indent");
+        let expected = [Anchor::End, Anchor::LineStart, Anchor::LineEnd, Anchor::Indent]
+            .map(|anchor| goto(Dest::Anchor(anchor)))
+            .to_vec();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_goto_marker_offset() {
+        let output = parse_ok("goto marker +2 4\ngoto marker -1, 0\ngoto marker\nclear");
+        let expected = vec![
+            goto(Dest::MarkerOffset {
+                name: "marker".into(),
+                row: 2,
+                col: 4,
+            }),
+            goto(Dest::MarkerOffset {
+                name: "marker".into(),
+                row: -1,
+                col: 0,
+            }),
+            goto("marker"),
+            Instruction::Clear,
+        ];
+        assert_eq!(output, expected);
+    }
+
     #[test]
     fn parse_type() {
         let output = parse_ok("type \"a string\"");
@@ -389,6 +777,131 @@ mod test {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn parse_seed() {
+        let output = parse_ok("seed 42");
+        let expected = vec![Instruction::Seed(42)];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_pause() {
+        let output = parse_ok("pause\nwaitkey \"press any key\"");
+        let expected = vec![
+            Instruction::Pause(None),
+            Instruction::Pause(Some("press any key".into())),
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_chapter() {
+        let output = parse_ok("chapter intro\nchapter \"the end\"");
+        let expected = vec![
+            Instruction::Chapter("intro".into()),
+            Instruction::Chapter("the end".into()),
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_on_finish() {
+        let output = parse_ok("onfinish hold\nonfinish loop\nonfinish exit");
+        let expected = vec![
+            Instruction::OnFinish(OnFinish::Hold),
+            Instruction::OnFinish(OnFinish::Loop),
+            Instruction::OnFinish(OnFinish::Exit),
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_include() {
+        let output = parse_ok("clear\ninclude \"preamble.echo\"");
+        let expected = vec![Instruction::Clear, Instruction::Include("preamble.echo".into())];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_let() {
+        let output = parse_ok("let crate_name = \"parrot\"");
+        let expected = vec![Instruction::Let {
+            name: "crate_name".into(),
+            value: "parrot".into(),
+        }];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_define_and_call() {
+        let output = parse_ok(
+            r#"
+define save {
+    popup "saved"
+    wait 1
+    closepopup
+}
+define greet name {
+    type "hello {name}"
+}
+call save
+call greet "world"
+"#,
+        );
+        let expected = vec![
+            Instruction::Define {
+                name: "save".into(),
+                params: vec![],
+                body: block(vec![
+                    Instruction::Popup("saved".into()),
+                    wait(1),
+                    Instruction::ClosePopup,
+                ]),
+            },
+            Instruction::Define {
+                name: "greet".into(),
+                params: vec!["name".into()],
+                body: block(vec![print_str("hello {name}")]),
+            },
+            Instruction::Call {
+                name: "save".into(),
+                args: vec![],
+            },
+            Instruction::Call {
+                name: "greet".into(),
+                args: vec!["world".into()],
+            },
+        ];
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn parse_repeat() {
+        let output = parse_ok("repeat 3 {\n    goto 1 0\n    repeat 2 {\n        delete\n    }\n}");
+        let expected = vec![Instruction::Repeat {
+            count: 3,
+            body: block(vec![
+                goto((1, 0)),
+                Instruction::Repeat {
+                    count: 2,
+                    body: block(vec![Instruction::Delete]),
+                },
+            ]),
+        }];
+        assert_eq!(output, expected);
+        assert_eq!(
+            parse_ok("repeat 0 {\n}"),
+            [Instruction::Repeat { count: 0, body: vec![] }]
+        );
+        let error = parse("repeat -1 {\n}").unwrap_err();
+        assert!(error.iter().next().unwrap().message().contains("non-negative int"));
+    }
+
+    #[test]
+    fn parse_unclosed_define() {
+        assert!(parse("define save {\nclear\n").is_err());
+    }
+
     #[test]
     fn parse_goto_negatives() {
         let output = parse_ok("goto -1 -2");
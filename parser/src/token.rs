@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
 #[derive(Debug, Default, PartialEq)]
 pub enum Token {
@@ -8,6 +9,8 @@ pub enum Token {
     Equal,
     At,
     AtAt,
+    LeftBrace,
+    RightBrace,
 
     // Multi char tokens
     As,
@@ -15,6 +18,8 @@ pub enum Token {
     Delete,
     Bool(bool),
     Int(i64),
+    Float(f64),
+    Duration(Duration),
     Str(String),
     Ident(String),
     Comment,
@@ -22,16 +27,25 @@ pub enum Token {
     NoNewline,
 
     // Actions
+    Call,
+    Chapter,
     Clear,
+    OnFinish,
     ClosePopup,
+    Define,
     Find,
     Goto,
+    Include,
     Insert,
     Jitter,
+    Let,
     LinePause,
     Load,
+    Pause,
     Popup,
+    Repeat,
     Replace,
+    Seed,
     Select,
     SetExtension,
     SetTitle,
@@ -56,6 +70,8 @@ impl Display for Token {
             Token::At => write!(f, "@"),
             Token::AtAt => write!(f, "@@"),
             Token::Equal => write!(f, "="),
+            Token::LeftBrace => write!(f, "{{"),
+            Token::RightBrace => write!(f, "}}"),
             Token::Bang => write!(f, "!"),
             Token::Newline => write!(f, "<nl>"),
 
@@ -63,21 +79,32 @@ impl Display for Token {
             Token::Delete => write!(f, "delete"),
             Token::Ident(s) => write!(f, "{s}"),
             Token::Int(int) => write!(f, "{int}"),
+            Token::Float(float) => write!(f, "{float}"),
+            Token::Duration(duration) => write!(f, "{duration:?}"),
             Token::NoNewline => write!(f, "no newline"),
             Token::Str(s) => write!(f, "\"{s}\""),
             Token::Bool(b) => write!(f, "{b}"),
 
             Token::Audio => write!(f, "audio"),
+            Token::Call => write!(f, "call"),
+            Token::Chapter => write!(f, "chapter"),
             Token::Clear => write!(f, "clear"),
+            Token::OnFinish => write!(f, "onfinish"),
             Token::ClosePopup => write!(f, "close popup"),
+            Token::Define => write!(f, "define"),
             Token::Find => write!(f, "find"),
             Token::Goto => write!(f, "goto"),
+            Token::Include => write!(f, "include"),
             Token::Insert => write!(f, "insert"),
             Token::Jitter => write!(f, "jitter"),
+            Token::Let => write!(f, "let"),
             Token::LinePause => write!(f, "line pause"),
             Token::Load => write!(f, "load"),
+            Token::Pause => write!(f, "pause"),
             Token::Popup => write!(f, "popup"),
+            Token::Repeat => write!(f, "repeat"),
             Token::Replace => write!(f, "change"),
+            Token::Seed => write!(f, "seed"),
             Token::Select => write!(f, "select"),
             Token::SetExtension => write!(f, "set extenion"),
             Token::SetTitle => write!(f, "set title"),
@@ -98,10 +125,13 @@ impl Display for Token {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub token: u32,
     pub line: u16,
     pub col: u16,
+    /// Byte offset into the source
+    pub offset: u32,
 }
 
 impl Span {
@@ -109,15 +139,26 @@ impl Span {
         token: 0,
         line: 1,
         col: 1,
+        offset: 0,
     };
 }
 
+impl Default for Span {
+    fn default() -> Self {
+        Self::INITIAL
+    }
+}
+
 #[derive(Debug)]
 pub struct Tokens<'src> {
     pub(crate) source: &'src str,
     index: usize,
+    // Index of the last token that was taken
+    last: usize,
     tokens: Vec<Token>,
     spans: Vec<Span>,
+    // True if the last token that was taken ended a line
+    after_newline: bool,
 }
 
 impl<'src> Tokens<'src> {
@@ -125,8 +166,10 @@ impl<'src> Tokens<'src> {
         Self {
             source,
             index: 0,
+            last: 0,
             tokens,
             spans,
+            after_newline: false,
         }
     }
 
@@ -141,6 +184,7 @@ impl<'src> Tokens<'src> {
     }
 
     pub(crate) fn consume(&mut self) {
+        self.after_newline = false;
         if self.index == self.tokens.len() - 1 {
             return;
         }
@@ -160,21 +204,59 @@ impl<'src> Tokens<'src> {
         }
     }
 
+    pub(crate) fn take_if(&mut self, f: impl Fn(&Token) -> bool) -> Option<Token> {
+        self.skip_pointelss_tokens();
+
+        match f(self.current()) {
+            true => Some(self.take()),
+            false => None,
+        }
+    }
+
     pub(crate) fn take(&mut self) -> Token {
         self.skip_pointelss_tokens();
 
-        let token = std::mem::take(&mut self.tokens[self.index]);
+        // Eof stays in place so the end of the input can always be found
+        let token = match self.tokens[self.index] {
+            Token::Eof => Token::Eof,
+            _ => std::mem::take(&mut self.tokens[self.index]),
+        };
+        self.last = self.index;
         self.consume();
+        self.after_newline = token == Token::Newline;
         token
     }
 
+    /// Skip the rest of the current line, to carry on parsing after an error
+    pub(crate) fn skip_line(&mut self) {
+        if self.after_newline {
+            return;
+        }
+
+        while !matches!(self.tokens[self.index], Token::Newline | Token::Eof) {
+            self.index += 1;
+        }
+        self.consume();
+    }
+
+    /// The start of the next token that isn't whitespace or a comment
+    pub(crate) fn span(&mut self) -> Span {
+        self.skip_pointelss_tokens();
+        self.spans[self.index]
+    }
+
+    /// The start of the last token that was taken, and the start of the token after it
     pub(crate) fn spans(&self) -> (Span, Span) {
-        let start = self.spans[self.index];
-        let end = if self.index + 1 == self.tokens.len() { start } else { self.spans[self.index + 1] };
+        let start = self.spans[self.last];
+        let end = self.spans.get(self.last + 1).copied().unwrap_or(start);
 
         (start, end)
     }
 
+    pub(crate) fn into_parts(self) -> (Vec<Token>, Vec<Span>) {
+        (self.tokens, self.spans)
+    }
+
     #[cfg(test)]
     pub fn take_tokens(self) -> Vec<Token> {
         self.tokens
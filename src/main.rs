@@ -1,7 +1,11 @@
 use std::env::args;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
 
-use parser::parse;
-use ui::compile;
+use anathema::geometry::Size;
+use parser::{Instructions, parse};
+use ui::{OnFinish, Options, check_script, compile_script};
 
 fn help() {
     println!(
@@ -10,9 +14,28 @@ Usage
 -----
 
 run:            parrot <file path>
+run headless:   parrot --headless <file path>
+dump as json:   parrot --dump-json <file path>
+check:          parrot check <file path>
+format:         parrot fmt [--check] <file path>...
+lsp server:     parrot lsp
+record:         parrot record <file path> [-o <output.cast|output.svg>] [--size <cols>x<rows>] [--format <cast|svg>]
 print syntaxes: parrot --syntax
 print themes:   parrot --themes
 
+options:
+  --seed <int>  seed the jitter so the timing is the same on every run,
+                this takes precedence over the `seed` instruction
+  --from <name> fast forward to a chapter or marker before the playback starts
+  --loop        play the script again once it is done
+  --exit-on-finish
+                return to the shell once the script is done
+  --hold        keep showing the document once the script is done (default),
+                these three take precedence over the `onfinish` instruction
+
+A script ending in `.json` is read as a list of instructions, in the format
+printed by `--dump-json`.
+
 example: parrot code.echo
 
 For more information see https://github.com/togglebyte/parrot
@@ -20,13 +43,64 @@ For more information see https://github.com/togglebyte/parrot
     );
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            report(error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Parse errors are coloured when they are printed to a terminal
+fn report(error: anyhow::Error) {
+    let colour = std::io::stderr().is_terminal();
+    if let Some(errors) = error.downcast_ref::<parser::Errors>() {
+        match colour {
+            true => eprintln!("Error: {errors:#}"),
+            false => eprintln!("Error: {errors}"),
+        }
+    } else if let Some(error) = error.downcast_ref::<ui::Error>() {
+        match colour {
+            true => eprintln!("Error: {error:#}"),
+            false => eprintln!("Error: {error}"),
+        }
+    } else {
+        eprintln!("Error: {error:?}");
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    let mut options = Options::default();
+    let mut rest = vec![];
+
     let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let seed = args.next().unwrap_or_default();
+                options.seed = Some(seed.parse().map_err(|_| anyhow::anyhow!("invalid seed \"{seed}\""))?);
+            }
+            "--from" => options.from = args.next(),
+            "--loop" => options.on_finish = Some(OnFinish::Loop),
+            "--exit-on-finish" => options.on_finish = Some(OnFinish::Exit),
+            "--hold" => options.on_finish = Some(OnFinish::Hold),
+            _ => rest.push(arg),
+        }
+    }
+
+    let mut args = rest.into_iter();
     let Some(arg) = args.next() else {
         help();
         return Ok(());
     };
 
+    if arg == "lsp" {
+        lsp::run()?;
+        return Ok(());
+    }
+
     ui::setup_paths::ensure_exists()?;
 
     if arg == "--syntax" {
@@ -39,9 +113,181 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let echo = std::fs::read_to_string(arg)?;
-    let instructions = parse(&echo)?;
-    let instructions = compile(instructions)?;
-    ui::run(instructions)?;
+    if arg == "--headless" {
+        let Some(path) = args.next() else {
+            help();
+            return Ok(());
+        };
+        let instructions = read_script(&path)?;
+        let instructions = compile_script(instructions, &path)?;
+        let output = ui::run_headless(instructions, options)?;
+        print!("{}", output.text);
+        return Ok(());
+    }
+
+    if arg == "--dump-json" {
+        let Some(path) = args.next() else {
+            help();
+            return Ok(());
+        };
+        let instructions = read_script(&path)?;
+        println!("{}", serde_json::to_string_pretty(&instructions)?);
+        return Ok(());
+    }
+
+    if arg == "record" {
+        return record(args, options);
+    }
+
+    if arg == "check" {
+        return check(args);
+    }
+
+    if arg == "fmt" {
+        return format(args);
+    }
+
+    let instructions = read_script(&arg)?;
+    let instructions = compile_script(instructions, &arg)?;
+    ui::run(instructions, options)?;
     Ok(())
 }
+
+// Parse a script, or read the instructions directly if it is a JSON file
+fn read_script(path: impl AsRef<Path>) -> anyhow::Result<Instructions> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)?;
+    match path.extension().is_some_and(|extension| extension == "json") {
+        true => Ok(serde_json::from_str(&content)?),
+        false => Ok(parse(&content)?),
+    }
+}
+
+// Report every problem in the script without playing it
+fn check(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let Some(path) = args.next() else {
+        help();
+        return Ok(());
+    };
+
+    let instructions = read_script(&path)?;
+    let problems = check_script(instructions, &path);
+    if problems.is_empty() {
+        println!("{path}: no problems found");
+        return Ok(());
+    }
+
+    let colour = std::io::stderr().is_terminal();
+    for problem in &problems {
+        match colour {
+            true => eprintln!("{problem:#}"),
+            false => eprintln!("{problem}"),
+        }
+    }
+    anyhow::bail!("{path}: found {} problem(s)", problems.len())
+}
+
+// Format the scripts in place, or with `--check` only list the ones that aren't formatted
+fn format(args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let (flags, paths): (Vec<_>, Vec<_>) = args.partition(|arg| arg.starts_with("--"));
+    let check = match flags.as_slice() {
+        [] => false,
+        [flag] if flag == "--check" => true,
+        [flag, ..] => anyhow::bail!("unknown argument \"{flag}\""),
+    };
+
+    if paths.is_empty() {
+        help();
+        return Ok(());
+    }
+
+    let mut unformatted = 0;
+    for path in &paths {
+        let echo = std::fs::read_to_string(path)?;
+        let formatted = parser::format(&echo)?;
+        if formatted == echo {
+            continue;
+        }
+
+        match check {
+            true => {
+                println!("{path}");
+                unformatted += 1;
+            }
+            false => std::fs::write(path, formatted)?,
+        }
+    }
+
+    if unformatted > 0 {
+        anyhow::bail!("{unformatted} file(s) need formatting");
+    }
+
+    Ok(())
+}
+
+fn record(mut args: impl Iterator<Item = String>, options: Options) -> anyhow::Result<()> {
+    let Some(path) = args.next() else {
+        help();
+        return Ok(());
+    };
+
+    let mut output = None;
+    let mut size = Size::new(80, 24);
+    let mut format = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next(),
+            "--size" => size = parse_size(&args.next().unwrap_or_default())?,
+            "--format" => format = args.next(),
+            arg => anyhow::bail!("unknown argument \"{arg}\""),
+        }
+    }
+
+    let instructions = read_script(&path)?;
+    let instructions = compile_script(instructions, &path)?;
+
+    // Default to the format given by the file extension
+    let format = format.unwrap_or_else(|| match output.as_deref() {
+        Some(path) if path.ends_with(".svg") => "svg".into(),
+        _ => "cast".into(),
+    });
+
+    let output: Box<dyn std::io::Write> = match output {
+        Some(output) => Box::new(std::fs::File::create(output)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    match format.as_str() {
+        "cast" => ui::record(instructions, options, size, output)?,
+        "svg" => ui::record_svg(instructions, options, size, output)?,
+        format => anyhow::bail!("unknown format \"{format}\", expected cast or svg"),
+    }
+
+    Ok(())
+}
+
+// `<cols>x<rows>`, with room for at least one row above the status bar
+fn parse_size(value: &str) -> anyhow::Result<Size> {
+    let Some((width, height)) = value.split_once('x') else {
+        anyhow::bail!("invalid size \"{value}\", expected <cols>x<rows>");
+    };
+    let size = Size::new(width.parse()?, height.parse()?);
+    if size.width < 1 || size.height < 2 {
+        anyhow::bail!("invalid size \"{value}\", expected at least 1 column and 2 rows");
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("80x24").unwrap(), Size::new(80, 24));
+        assert!(parse_size("0x5").is_err());
+        assert!(parse_size("5x1").is_err());
+        assert!(parse_size("80").is_err());
+    }
+}
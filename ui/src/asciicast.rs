@@ -0,0 +1,151 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use anathema::geometry::Size;
+
+use crate::error::Result;
+use crate::instructions::Located;
+use crate::options::Options;
+use crate::screen::{Color, Renderer, Screen, Style, render_frames};
+use crate::syntax::Highlighter;
+
+/// Play the instructions back without a terminal and write every frame
+/// to `output` as an asciicast v2 recording.
+///
+/// See <https://docs.asciinema.org/manual/asciicast/v2/>
+pub fn record(instructions: Vec<Located>, options: Options, size: Size, mut output: impl Write) -> Result<()> {
+    let renderer = Renderer::new(Highlighter::new(), size);
+
+    writeln!(
+        output,
+        "{{\"version\": 2, \"width\": {}, \"height\": {}}}",
+        size.width, size.height
+    )?;
+
+    let mut first = true;
+    render_frames(instructions, options, &renderer, |timestamp, screen| {
+        let mut frame = String::new();
+        if first {
+            // Hide the cursor and clear the screen
+            frame.push_str("\x1b[?25l\x1b[2J");
+            first = false;
+        }
+        write_frame(&mut frame, screen);
+        writeln!(
+            output,
+            "[{:.6}, \"o\", {}]",
+            timestamp.as_secs_f64(),
+            json_string(&frame)
+        )?;
+        Ok(())
+    })?;
+
+    output.flush()?;
+    Ok(())
+}
+
+fn write_frame(frame: &mut String, screen: &Screen) {
+    frame.push_str("\x1b[H");
+
+    for (y, row) in screen.rows().enumerate() {
+        if y > 0 {
+            frame.push_str("\r\n");
+        }
+
+        let mut current = None;
+        for cell in row.iter().filter(|cell| !cell.is_continuation()) {
+            if current != Some(cell.style) {
+                write_style(frame, cell.style);
+                current = Some(cell.style);
+            }
+            frame.push(cell.c);
+        }
+        frame.push_str("\x1b[0m");
+    }
+}
+
+fn write_style(frame: &mut String, style: Style) {
+    frame.push_str("\x1b[0");
+    if style.bold {
+        frame.push_str(";1");
+    }
+    if style.italic {
+        frame.push_str(";3");
+    }
+    match style.fg {
+        Some(Color::Rgb(r, g, b)) => _ = write!(frame, ";38;2;{r};{g};{b}"),
+        Some(color) => _ = write!(frame, ";{}", 30 + ansi_index(color)),
+        None => {}
+    }
+    match style.bg {
+        Some(Color::Rgb(r, g, b)) => _ = write!(frame, ";48;2;{r};{g};{b}"),
+        Some(color) => _ = write!(frame, ";{}", 40 + ansi_index(color)),
+        None => {}
+    }
+    frame.push('m');
+}
+
+fn ansi_index(color: Color) -> u8 {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Grey => 7,
+        Color::Rgb(..) => unreachable!("rgb colours are written as 24 bit colours"),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut output = String::with_capacity(s.len() + 2);
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => _ = write!(output, "\\u{:04x}", c as u32),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_json() {
+        let actual = json_string("\x1b[0m\"a\\b\"\r\n");
+        let expected = r#""\u001b[0m\"a\\b\"\r\n""#;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn record_script() {
+        let src = "jitter 0\nspeed 100\nlinepause 300\ntype \"a\\nb\"\nwait 1\ntype \"c\"";
+        let instructions = crate::compile(parser::parse(src).unwrap()).unwrap();
+        let mut output = vec![];
+        record(instructions, Options::default(), Size::new(20, 4), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some(r#"{"version": 2, "width": 20, "height": 4}"#));
+
+        let events = lines.collect::<Vec<_>>();
+        assert!(events[0].starts_with(r#"[0.000000, "o", "\u001b[?25l\u001b[2J"#));
+        assert!(events[1..].iter().all(|event| !event.contains("[?25l")));
+
+        // Only frames that change the screen are written: a key press every 100ms,
+        // the line pause after the newline and the wait before the last key
+        let timestamps = events
+            .iter()
+            .map(|event| event[1..].split(',').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, ["0.000000", "0.370000", "0.470000", "0.770000", "1.970000"]);
+        assert!(events.last().unwrap().contains('c'));
+    }
+}
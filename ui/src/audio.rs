@@ -9,19 +9,44 @@ use crate::error::{Error, Result};
 
 pub struct AudioShell {
     audio: Option<Audio>,
+    enabled: bool,
+    muted: bool,
 }
 
 impl AudioShell {
     pub fn new() -> Self {
-        Self { audio: None }
+        Self {
+            audio: None,
+            enabled: true,
+            muted: false,
+        }
+    }
+
+    // An audio shell that ignores `load`, so no audio device is ever opened
+    pub fn disabled() -> Self {
+        Self {
+            audio: None,
+            enabled: false,
+            muted: false,
+        }
     }
 
     pub fn load(&mut self, path: PathBuf) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
         self.audio = Some(Audio::load(path)?);
         Ok(())
     }
 
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
     pub fn play(&mut self, name: &str) {
+        if self.muted {
+            return;
+        }
         let Some(audio) = self.audio.as_mut() else { return };
         audio.play(name);
     }
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use crate::compile::compile_all;
+use crate::editor::Editor;
+use crate::error::Error;
+use crate::instructions::{Instruction, Located};
+use crate::options::Options;
+use crate::syntax::Highlighter;
+
+/// Look for anything in a script that would stop the playback part way through:
+/// files and values that don't exist, markers that are never created,
+/// text to replace that isn't there, unknown themes and incomplete audio directories.
+///
+/// The script is compiled and then played back headlessly, carrying on past every problem.
+/// Returns every problem that was found, in the order they appear in the script.
+pub fn check_script(parsed_instructions: parser::Instructions, path: impl AsRef<Path>) -> Vec<Error> {
+    let (instructions, mut problems) = compile_all(parsed_instructions, path.as_ref());
+
+    let highlighter = Highlighter::new();
+    for Located { instruction, location } in &instructions {
+        let problem = match instruction {
+            Instruction::SetTheme(theme) if !highlighter.has_theme(theme) => Error::InvalidTheme(theme.clone()),
+            Instruction::LoadAudio(path) if !path.is_dir() => Error::FilePath(path.clone()),
+            Instruction::LoadAudio(path) if !path.join("default.mp3").is_file() => Error::NoDefaultSound,
+            _ => continue,
+        };
+        problems.push(problem.at(location.clone()));
+    }
+
+    problems.extend(dry_run(instructions));
+    problems.sort_by_key(|problem| match problem {
+        Error::At(location, _) => Some((location.path().to_path_buf(), location.line(), location.col())),
+        _ => None,
+    });
+    problems
+}
+
+// Play the instructions back, collecting every error instead of stopping at the first one
+fn dry_run(instructions: Vec<Located>) -> Vec<Error> {
+    let mut editor = Editor::strict(instructions, Options::default());
+    let mut problems = vec![];
+
+    while !editor.is_done() {
+        if let Err(error) = editor.advance() {
+            problems.push(error);
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Every test checks its own file, named after the test, so they can run at the same time
+    fn check(name: &str, src: &str) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("parrot-check-{name}-{}.echo", std::process::id()));
+        std::fs::write(&path, src).unwrap();
+        let problems = check_script(parser::parse(src).unwrap(), &path);
+        std::fs::remove_file(path).unwrap();
+
+        problems
+            .into_iter()
+            .map(|problem| match problem {
+                Error::At(location, error) => format!("{}: {error}", location.line()),
+                error => error.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn report_every_problem() {
+        let src = r#"
+load "does-not-exist.rs" as code
+type code
+type "fn main() {}"
+replace "nope" "yes"
+replace "x" nope
+goto missing
+audio "no-such-dir"
+"#;
+        let problems = check("report_every_problem", src);
+        // The audio directory is relative to the script
+        let audio = std::env::temp_dir().join("no-such-dir");
+        assert_eq!(
+            problems,
+            [
+                "2: failed to load \"does-not-exist.rs\"".to_string(),
+                "3: \"code\" does not exist".into(),
+                "5: \"nope\" is not on the cursor line".into(),
+                "6: \"nope\" does not exist".into(),
+                "7: marker \"missing\" does not exist".into(),
+                format!("8: file does not exist: {}", audio.display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn one_report_per_error() {
+        let problems = check("one_report_per_error", "type \"a\"\nreplace \"b\" nope\ntype \"c\"");
+        assert_eq!(problems, ["2: \"nope\" does not exist"]);
+    }
+
+    #[test]
+    fn valid_script() {
+        let problems = check(
+            "valid_script",
+            "type \"// @here\\nfn main() {}\"\ngoto here\nreplace \"main\" \"pain\"",
+        );
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+}
@@ -1,97 +1,416 @@
-use std::time::Duration;
+use std::path::{Path, PathBuf};
 
-use anathema::geometry::Size;
+use anathema::geometry::{Pos, Size};
 use parser::{Dest, Source};
 use unicode_width::UnicodeWidthStr;
 
 pub use crate::context::Context;
 use crate::error::{Error, Result};
-use crate::instructions::Instruction;
+use crate::instructions::{Instruction, Located};
 
-pub fn compile(parsed_instructions: parser::Instructions) -> Result<Vec<Instruction>> {
+pub fn compile(parsed_instructions: parser::Instructions) -> Result<Vec<Located>> {
     let mut context = Context::new();
-    let mut instructions = vec![];
+    let mut output = vec![];
+    context.begin_script(None, parsed_instructions.source())?;
+    compile_into(parsed_instructions, &mut context, &mut output)?;
+    Ok(output)
+}
 
-    for inst in parsed_instructions {
-        match inst {
-            parser::Instruction::Load(path, key) => {
-                let content = std::fs::read_to_string(&path).map_err(|_| Error::Import(path))?;
-                context.set(key, content);
-            }
-            parser::Instruction::Find(needle) => instructions.push(Instruction::FindInCurrentLine(needle)),
-            parser::Instruction::Goto(dest) => {
-                let inst = match dest {
-                    Dest::Relative { row, col } => Instruction::Jump((col, row).into()),
-                    Dest::Marker(name) => Instruction::JumpToMarker(name),
-                };
-                instructions.push(inst);
-            }
-            parser::Instruction::Select { width, height } => {
-                instructions.push(Instruction::Select(Size::new(width, height)))
-            }
-            parser::Instruction::Delete => instructions.push(Instruction::Delete),
-            parser::Instruction::Type {
-                source,
-                trim_trailing_newline,
-                prefix_newline,
-            } => {
-                let mut content = match source {
-                    Source::Str(content) => content,
-                    Source::Ident(key) => context.load(key)?,
-                };
-
-                if trim_trailing_newline && content.ends_with('\n') {
-                    _ = content.pop();
-                }
+/// Compile a script that was read from `path`.
+/// Any `include` is resolved relative to the directory of the script.
+pub fn compile_script(parsed_instructions: parser::Instructions, path: impl AsRef<Path>) -> Result<Vec<Located>> {
+    let mut context = Context::new();
+    let mut output = vec![];
+    context.begin_script(Some(path.as_ref().into()), parsed_instructions.source())?;
+    compile_into(parsed_instructions, &mut context, &mut output)?;
+    Ok(output)
+}
+
+/// Compile a script that was read from `path`, carrying on past any errors.
+/// Every error is returned along with whatever could be compiled.
+pub(crate) fn compile_all(parsed_instructions: parser::Instructions, path: &Path) -> (Vec<Located>, Vec<Error>) {
+    let mut context = Context::new();
+    let mut output = vec![];
+    context.collect_errors();
 
-                if prefix_newline {
-                    instructions.push(Instruction::Insert("\n".into()));
+    let res = context
+        .begin_script(Some(path.into()), parsed_instructions.source())
+        .and_then(|()| compile_into(parsed_instructions, &mut context, &mut output));
+
+    let mut errors = context.take_errors();
+    errors.extend(res.err());
+    (output, errors)
+}
+
+// Every instruction is compiled along with its location in the script,
+// and any error is pointed at the instruction that caused it.
+fn compile_into(
+    parsed_instructions: impl IntoIterator<Item = parser::Spanned>,
+    context: &mut Context,
+    output: &mut Vec<Located>,
+) -> Result<()> {
+    for parser::Spanned {
+        instruction,
+        start,
+        end,
+    } in parsed_instructions
+    {
+        let location = context.location(start, end);
+        let mut instructions = vec![];
+        // A failed instruction adds nothing, so a dry run doesn't play half of it
+        if let Err(error) = compile_instruction(instruction, start.line, context, &mut instructions, output) {
+            context.fail(error.at(location.clone()))?;
+            continue;
+        }
+
+        let located = instructions.into_iter().map(|instruction| Located {
+            instruction,
+            location: location.clone(),
+        });
+        output.extend(located);
+    }
+
+    Ok(())
+}
+
+// Compile a single instruction into `instructions`.
+// Instructions that expand to other instructions (include, call and repeat)
+// are compiled straight into `output` instead, with their own locations.
+fn compile_instruction(
+    instruction: parser::Instruction,
+    line: u16,
+    context: &mut Context,
+    instructions: &mut Vec<Instruction>,
+    output: &mut Vec<Located>,
+) -> Result<()> {
+    match instruction {
+        parser::Instruction::Load(path, key) => {
+            let content = std::fs::read_to_string(resolve(&path, context)).map_err(|_| Error::Import(path))?;
+            context.set(key, content);
+        }
+        parser::Instruction::Include(path) => {
+            let file = context.current_file().unwrap_or(Path::new("<script>")).to_path_buf();
+            include(path, context, output).map_err(|error| Error::Include {
+                file,
+                line,
+                error: Box::new(error),
+            })?;
+        }
+        parser::Instruction::Let { name, value } => {
+            let value = context.interpolate(value);
+            context.set(name, value);
+        }
+        parser::Instruction::Find(needle) => instructions.push(Instruction::FindInCurrentLine(needle)),
+        parser::Instruction::Goto(dest) => {
+            let inst = match dest {
+                Dest::Relative { row, col } => Instruction::Jump((col, row).into()),
+                Dest::Marker(name) => Instruction::JumpToMarker {
+                    name,
+                    offset: Pos::ZERO,
+                },
+                Dest::MarkerOffset { name, row, col } => Instruction::JumpToMarker {
+                    name,
+                    offset: (col, row).into(),
+                },
+                Dest::Absolute { line, col } => {
+                    let col = i32::try_from(col).unwrap_or(i32::MAX) - 1;
+                    let line = i32::try_from(line).unwrap_or(i32::MAX) - 1;
+                    Instruction::JumpTo(Pos::new(col, line))
                 }
-                instructions.push(Instruction::LoadTypeBuffer(content));
-            }
-            parser::Instruction::Insert(source) => {
-                let inst = match source {
-                    Source::Str(content) => Instruction::Insert(content),
-                    Source::Ident(key) => {
-                        let content = context.load(key)?;
-                        Instruction::Insert(content)
-                    }
-                };
-                instructions.push(inst);
+                Dest::Anchor(anchor) => Instruction::JumpToAnchor(anchor),
+            };
+            instructions.push(inst);
+        }
+        parser::Instruction::Select { width, height } => {
+            instructions.push(Instruction::Select(Size::new(width, height)))
+        }
+        parser::Instruction::Delete => instructions.push(Instruction::Delete),
+        parser::Instruction::Type {
+            source,
+            trim_trailing_newline,
+            prefix_newline,
+        } => {
+            let mut content = match source {
+                Source::Str(content) => context.interpolate(content),
+                Source::Ident(key) => context.load(key)?,
+            };
+
+            if trim_trailing_newline && content.ends_with('\n') {
+                _ = content.pop();
             }
-            parser::Instruction::Replace { src, replacement } => {
-                let width = src.width() as u16;
-                instructions.push(Instruction::FindInCurrentLine(src));
-                instructions.push(Instruction::Select(Size::new(width, 1)));
-                instructions.push(Instruction::Delete);
-                let inst = match replacement {
-                    Source::Str(content) => Instruction::LoadTypeBuffer(content),
-                    Source::Ident(key) => {
-                        let content = context.load(key).unwrap();
-                        Instruction::LoadTypeBuffer(content)
-                    }
-                };
-                instructions.push(inst);
+
+            if prefix_newline {
+                instructions.push(Instruction::Insert("\n".into()));
             }
-            parser::Instruction::Wait(seconds) => instructions.push(Instruction::Wait(Duration::from_secs(seconds))),
-            parser::Instruction::Speed(millis) => instructions.push(Instruction::Speed(Duration::from_millis(millis))),
-            parser::Instruction::LinePause(millis) => {
-                instructions.push(Instruction::LinePause(Duration::from_millis(millis)))
+            instructions.push(Instruction::LoadTypeBuffer(content));
+        }
+        parser::Instruction::Insert(source) => {
+            let inst = match source {
+                Source::Str(content) => Instruction::Insert(context.interpolate(content)),
+                Source::Ident(key) => {
+                    let content = context.load(key)?;
+                    Instruction::Insert(content)
+                }
+            };
+            instructions.push(inst);
+        }
+        parser::Instruction::Replace { src, replacement } => {
+            let src = context.interpolate(src);
+            let width = src.width() as u16;
+            instructions.push(Instruction::FindInCurrentLine(src));
+            instructions.push(Instruction::Select(Size::new(width, 1)));
+            instructions.push(Instruction::Delete);
+            let inst = match replacement {
+                Source::Str(content) => Instruction::LoadTypeBuffer(context.interpolate(content)),
+                Source::Ident(key) => {
+                    let content = context.load(key)?;
+                    Instruction::LoadTypeBuffer(content)
+                }
+            };
+            instructions.push(inst);
+        }
+        parser::Instruction::Wait(duration) => instructions.push(Instruction::Wait(duration)),
+        parser::Instruction::Speed(duration) => instructions.push(Instruction::Speed(duration)),
+        parser::Instruction::LinePause(duration) => instructions.push(Instruction::LinePause(duration)),
+        parser::Instruction::SetTitle(title) => instructions.push(Instruction::SetTitle(title)),
+        parser::Instruction::SetExtension(ext) => instructions.push(Instruction::SetExtension(ext)),
+        parser::Instruction::ShowLineNumbers(show) => instructions.push(Instruction::ShowLineNumbers(show)),
+        parser::Instruction::Jitter(jitter) => instructions.push(Instruction::SetJitter(jitter)),
+        parser::Instruction::Seed(seed) => instructions.push(Instruction::Seed(seed)),
+        parser::Instruction::SetTheme(theme) => instructions.push(Instruction::SetTheme(theme)),
+        parser::Instruction::LoadAudio(path) => instructions.push(Instruction::LoadAudio(resolve(&path, context))),
+        parser::Instruction::Clear => instructions.push(Instruction::Clear),
+        parser::Instruction::Popup(msg) => instructions.push(Instruction::Popup(context.interpolate(msg))),
+        parser::Instruction::ClosePopup => instructions.push(Instruction::ClosePopup),
+        parser::Instruction::Pause(hint) => instructions.push(Instruction::WaitForKey(hint)),
+        parser::Instruction::Chapter(name) => instructions.push(Instruction::Chapter(name)),
+        parser::Instruction::OnFinish(on_finish) => instructions.push(Instruction::OnFinish(on_finish)),
+        parser::Instruction::Define { name, params, body } => context.define(name, params, body),
+        parser::Instruction::Call { name, args } => call(name, args, context, output)?,
+        parser::Instruction::Repeat { count, body } => {
+            for _ in 0..count {
+                compile_into(body.clone(), context, output)?;
             }
-            parser::Instruction::SetTitle(title) => instructions.push(Instruction::SetTitle(title)),
-            parser::Instruction::SetExtension(ext) => instructions.push(Instruction::SetExtension(ext)),
-            parser::Instruction::ShowLineNumbers(show) => instructions.push(Instruction::ShowLineNumbers(show)),
-            parser::Instruction::Jitter(jitter) => instructions.push(Instruction::SetJitter(jitter)),
-            parser::Instruction::SetTheme(theme) => instructions.push(Instruction::SetTheme(theme)),
-            parser::Instruction::LoadAudio(path) => instructions.push(Instruction::LoadAudio(path)),
-            parser::Instruction::Clear => instructions.push(Instruction::Clear),
-            parser::Instruction::Popup(msg) => instructions.push(Instruction::Popup(msg)),
-            parser::Instruction::ClosePopup => instructions.push(Instruction::ClosePopup),
         }
     }
 
-    Ok(instructions)
+    for instruction in instructions.iter() {
+        if let Instruction::LoadTypeBuffer(content) | Instruction::Insert(content) = instruction {
+            reserved_markers(content)?;
+        }
+    }
+
+    Ok(())
+}
+
+// `goto end`, `goto line` and the like would never reach a marker of the same name
+fn reserved_markers(content: &str) -> Result<()> {
+    let (_, markers) = crate::markers::generate(content);
+    match markers
+        .into_iter()
+        .flatten()
+        .find(|marker| Dest::is_reserved(marker.name()))
+    {
+        Some(marker) => Err(Error::ReservedMarker(marker.name().into())),
+        None => Ok(()),
+    }
+}
+
+// Paths are relative to the directory of the script they are in
+fn resolve(path: &Path, context: &Context) -> PathBuf {
+    match context.current_file().and_then(Path::parent) {
+        Some(dir) => dir.join(path),
+        None => path.to_path_buf(),
+    }
+}
+
+// Compile another script into the same instructions, sharing bindings and macros
+fn include(path: PathBuf, context: &mut Context, output: &mut Vec<Located>) -> Result<()> {
+    let path = resolve(&path, context);
+
+    let src = std::fs::read_to_string(&path).map_err(|_| Error::Import(path.clone()))?;
+    let parsed = parser::parse(&src).map_err(|e| Error::Parse(path.clone(), e))?;
+
+    context.begin_script(Some(path), parsed.source())?;
+    let res = compile_into(parsed, context, output);
+    context.end_script();
+    res
+}
+
+// Expand a macro with its parameters bound to the arguments.
+// The arguments are only bound for the duration of the call.
+fn call(name: String, args: Vec<String>, context: &mut Context, output: &mut Vec<Located>) -> Result<()> {
+    let mac = context.begin_call(&name)?;
+    if mac.params.len() != args.len() {
+        context.end_call(&mac);
+        return Err(Error::MacroArgs {
+            name,
+            expected: mac.params.len(),
+            found: args.len(),
+        });
+    }
+
+    let args = args.into_iter().map(|arg| context.interpolate(arg)).collect::<Vec<_>>();
+    let previous = mac
+        .params
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| (param.clone(), context.swap(param.clone(), Some(arg))))
+        .collect::<Vec<_>>();
+
+    let res = compile_into(mac.body.clone(), context, output);
+
+    for (param, value) in previous {
+        context.swap(param, value);
+    }
+    context.end_call(&mac);
+
+    res
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    fn compile_str(src: &str) -> Result<Vec<Located>> {
+        compile(parser::parse(src).unwrap())
+    }
+
+    fn typed(instructions: &[Located]) -> Vec<&str> {
+        instructions
+            .iter()
+            .filter_map(|located| match &located.instruction {
+                Instruction::LoadTypeBuffer(content) => Some(content.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn include_relative_to_script() {
+        let dir = std::env::temp_dir().join(format!("parrot-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("chapters")).unwrap();
+        std::fs::write(dir.join("preamble.echo"), "let name = \"parrot\"").unwrap();
+        std::fs::write(
+            dir.join("chapters/one.echo"),
+            "include \"../preamble.echo\"\ntype \"{name}\"",
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.echo"), "clear\ninclude \"b.echo\"").unwrap();
+        std::fs::write(dir.join("b.echo"), "include \"a.echo\"").unwrap();
+
+        let path = dir.join("chapters/one.echo");
+        let parsed = parser::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let instructions = compile_script(parsed, &path).unwrap();
+        assert_eq!(typed(&instructions), ["parrot"]);
+
+        let path = dir.join("a.echo");
+        let parsed = parser::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let err = compile_script(parsed, &path).unwrap_err();
+        let expected = format!(
+            "{0}:2: {1}:1: \"{0}\" ends up including itself",
+            path.display(),
+            dir.join("b.echo").display()
+        );
+        assert_eq!(err.to_string(), expected);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_relative_to_script() {
+        let dir = std::env::temp_dir().join(format!("parrot-load-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("chapters")).unwrap();
+        std::fs::write(dir.join("shared.rs"), "shared").unwrap();
+        std::fs::write(dir.join("shared.echo"), "load \"shared.rs\" as shared").unwrap();
+        std::fs::write(dir.join("chapters/one.rs"), "one").unwrap();
+        std::fs::write(
+            dir.join("chapters/one.echo"),
+            "include \"../shared.echo\"\nload \"one.rs\" as one\ntype shared\ntype one\naudio \"sounds\"",
+        )
+        .unwrap();
+
+        let path = dir.join("chapters/one.echo");
+        let parsed = parser::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let instructions = compile_script(parsed, &path).unwrap();
+        assert_eq!(typed(&instructions), ["shared", "one"]);
+        let audio = instructions.iter().find_map(|located| match &located.instruction {
+            Instruction::LoadAudio(path) => Some(path.clone()),
+            _ => None,
+        });
+        assert_eq!(audio, Some(dir.join("chapters/sounds")));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn repeat() {
+        let instructions = compile_str("repeat 2 {\ntype \"a\"\nrepeat 2 {\ntype \"b\"\n}\n}").unwrap();
+        assert_eq!(typed(&instructions), ["a", "b", "b", "a", "b", "b"]);
+    }
+
+    #[test]
+    fn call_macro() {
+        let src = r#"
+let name = "outer"
+define greet name {
+    type "hello {name}"
+}
+call greet "world"
+call greet "{name}"
+type "{name}"
+"#;
+        let instructions = compile_str(src).unwrap();
+        assert_eq!(typed(&instructions), ["hello world", "hello outer", "outer"]);
+    }
+
+    // The line of the instruction that caused the error, and the error itself
+    fn located_err(src: &str) -> (u16, String) {
+        match compile_str(src).unwrap_err() {
+            Error::At(location, error) => (location.line(), error.to_string()),
+            error => panic!("error without a location: {error}"),
+        }
+    }
+
+    #[test]
+    fn call_macro_errors() {
+        let err = located_err("call nope");
+        assert_eq!(err, (1, "no macro named \"nope\" has been defined".into()));
+
+        let err = located_err("define a x {\nclear\n}\ncall a");
+        assert_eq!(err, (4, "macro \"a\" takes 1 argument(s) but 0 were given".into()));
+
+        // The error points at the call inside the macro body
+        let err = located_err("define a {\ncall b\n}\ndefine b {\ncall a\n}\ncall a");
+        assert_eq!(err, (5, "macro \"a\" calls itself".into()));
+    }
+
+    #[test]
+    fn missing_value_location() {
+        let err = located_err("clear\ntype nope");
+        assert_eq!(err, (2, "\"nope\" does not exist".into()));
+    }
+
+    #[test]
+    fn reserved_marker_names() {
+        let err = located_err("clear\ntype \"fn main() {} // @end\"\ngoto end");
+        assert_eq!(
+            err,
+            (
+                2,
+                "\"end\" can't name a marker, `goto end` doesn't go to markers".into()
+            )
+        );
+
+        let err = located_err("insert \"// @line\\n\"");
+        assert_eq!(
+            err,
+            (
+                1,
+                "\"line\" can't name a marker, `goto line` doesn't go to markers".into()
+            )
+        );
+
+        assert!(compile_str("insert \"// @ending\\n\"\ngoto ending").is_ok());
+    }
+}
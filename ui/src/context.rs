@@ -1,13 +1,60 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parser::Span;
 
 use crate::error::{Error, Result};
+use crate::location::{Location, Script};
 
 pub struct Context {
     data: HashMap<String, String>,
+    macros: HashMap<String, Macro>,
+    // Names of the macros that are currently being expanded
+    calls: Vec<String>,
+    // The scripts that are currently being compiled, the innermost last
+    scripts: Vec<Arc<Script>>,
+    // Set to carry on compiling past errors, collecting them instead
+    errors: Option<Vec<Error>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Macro {
+    pub(crate) params: Vec<String>,
+    pub(crate) body: Vec<parser::Spanned>,
+    // The script the macro was defined in
+    pub(crate) script: Option<Arc<Script>>,
 }
+
 impl Context {
     pub(crate) fn new() -> Self {
-        Self { data: HashMap::new() }
+        Self {
+            data: HashMap::new(),
+            macros: HashMap::new(),
+            calls: vec![],
+            scripts: vec![],
+            errors: None,
+        }
+    }
+
+    // Collect errors with `fail` rather than stopping at the first one
+    pub(crate) fn collect_errors(&mut self) {
+        self.errors = Some(vec![]);
+    }
+
+    // Record the error if errors are being collected, otherwise return it
+    pub(crate) fn fail(&mut self, error: Error) -> Result<()> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(error);
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    pub(crate) fn take_errors(&mut self) -> Vec<Error> {
+        self.errors.take().unwrap_or_default()
     }
 
     pub fn set(&mut self, key: String, value: String) {
@@ -18,4 +65,156 @@ impl Context {
         let key = key.as_ref();
         self.data.get(key).cloned().ok_or_else(|| Error::LoadValue(key.into()))
     }
+
+    // Set the value of `key`, or remove it if there is no value,
+    // returning the previous value
+    pub(crate) fn swap(&mut self, key: String, value: Option<String>) -> Option<String> {
+        match value {
+            Some(value) => self.data.insert(key, value),
+            None => self.data.remove(&key),
+        }
+    }
+
+    pub(crate) fn define(&mut self, name: String, params: Vec<String>, body: Vec<parser::Spanned>) {
+        let script = self.scripts.last().cloned();
+        self.macros.insert(name, Macro { params, body, script });
+    }
+
+    // Look up a macro and mark it as being expanded until `end_call` is called.
+    // The body is compiled as part of the script the macro was defined in.
+    pub(crate) fn begin_call(&mut self, name: &str) -> Result<Macro> {
+        if self.calls.iter().any(|call| call == name) {
+            return Err(Error::RecursiveMacro(name.into()));
+        }
+
+        let mac = self
+            .macros
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::MissingMacro(name.into()))?;
+        self.calls.push(name.into());
+        if let Some(script) = &mac.script {
+            self.scripts.push(script.clone());
+        }
+        Ok(mac)
+    }
+
+    pub(crate) fn end_call(&mut self, mac: &Macro) {
+        self.calls.pop();
+        if mac.script.is_some() {
+            self.scripts.pop();
+        }
+    }
+
+    /// The script that is currently being compiled, if it came from a file
+    pub(crate) fn current_file(&self) -> Option<&Path> {
+        self.scripts.last().and_then(|script| script.path.as_deref())
+    }
+
+    /// Where the range is in the script that is currently being compiled
+    pub(crate) fn location(&self, start: Span, end: Span) -> Option<Location> {
+        let script = self.scripts.last()?;
+        Some(Location::new(script.clone(), start, end))
+    }
+
+    // Mark the script as being compiled until `end_script` is called
+    pub(crate) fn begin_script(&mut self, path: Option<PathBuf>, source: impl Into<String>) -> Result<()> {
+        if let Some(path) = &path {
+            // Compare canonical paths so `a.echo` and `./a.echo` are the same file
+            let canonical = path.canonicalize().map_err(|_| Error::Import(path.clone()))?;
+            let cycle = self
+                .scripts
+                .iter()
+                .filter_map(|script| script.path.as_ref())
+                .any(|file| file.canonicalize().is_ok_and(|file| file == canonical));
+            if cycle {
+                return Err(Error::IncludeCycle(path.clone()));
+            }
+        }
+
+        let source = source.into();
+        self.scripts.push(Arc::new(Script { path, source }));
+        Ok(())
+    }
+
+    pub(crate) fn end_script(&mut self) {
+        self.scripts.pop();
+    }
+
+    /// Replace every `{name}` in the input with the value bound to `name`.
+    /// Braces around anything that isn't a bound name are left as they are,
+    /// so code like `fn main() {}` or `println!("{x}")` is typed out untouched.
+    /// `{{name}}` is typed as `{name}`, even if `name` is bound.
+    pub fn interpolate(&self, input: impl AsRef<str>) -> String {
+        let mut input = input.as_ref();
+        let mut output = String::with_capacity(input.len());
+
+        while let Some(start) = input.find('{') {
+            output.push_str(&input[..start]);
+            input = &input[start..];
+
+            let escaped = input
+                .strip_prefix("{{")
+                .and_then(|rest| rest.split_once("}}"))
+                .map(|(name, _)| name)
+                .filter(|name| is_name(name));
+            if let Some(name) = escaped {
+                output.push_str(&format!("{{{name}}}"));
+                input = &input[name.len() + 4..];
+                continue;
+            }
+
+            let value = input[1..]
+                .split_once('}')
+                .map(|(name, _)| name)
+                .filter(|name| is_name(name))
+                .and_then(|name| Some((name, self.data.get(name)?)));
+
+            match value {
+                Some((name, value)) => {
+                    output.push_str(value);
+                    input = &input[name.len() + 2..];
+                }
+                None => {
+                    output.push('{');
+                    input = &input[1..];
+                }
+            }
+        }
+
+        output.push_str(input);
+        output
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interpolate() {
+        let mut context = Context::new();
+        context.set("name".into(), "main".into());
+        context.set("crate-name".into(), "parrot".into());
+
+        let actual = context.interpolate("fn {name}() { {crate-name}::run(\"{x}\") }{");
+        assert_eq!(actual, "fn main() { parrot::run(\"{x}\") }{");
+    }
+
+    #[test]
+    fn interpolate_escaped() {
+        let mut context = Context::new();
+        context.set("name".into(), "parrot".into());
+
+        let actual = context.interpolate("println!(\"{{name}}\"); // {name}");
+        assert_eq!(actual, "println!(\"{name}\"); // parrot");
+
+        // Only names are escaped
+        let actual = context.interpolate("{{}} {{ {name} }}");
+        assert_eq!(actual, "{{}} {{ parrot }}");
+    }
 }
@@ -1,11 +1,12 @@
 use std::ops::Range;
 
 use anathema::geometry::{Pos, Region};
-use unicode_width::UnicodeWidthChar;
+use parser::Anchor;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::markers::{Marker, Markers};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Document {
     pub markers: Markers,
     text: String,
@@ -18,8 +19,8 @@ impl Document {
         Self { text, markers }
     }
 
-    pub fn add_markers(&mut self, row: usize, markers: Markers) {
-        self.markers.merge(row, markers);
+    pub fn add_markers(&mut self, row: usize, col: usize, markers: Markers) {
+        self.markers.merge(row, col, markers);
     }
 
     pub fn text(&self) -> &str {
@@ -54,12 +55,7 @@ impl Document {
         let s = s.as_ref();
         let index = self.byte_offset(pos);
         self.text.insert_str(index, s);
-
-        // If the string contains a newline character then offset all the markers by one
-        let newlines = s.chars().filter(|c| *c == '\n').count();
-        if newlines > 0 {
-            self.markers.offset_after(pos.y as usize, newlines);
-        }
+        self.markers.insert(pos.y as usize, pos.x as usize, s);
     }
 
     // Get the byte position in the string
@@ -89,12 +85,17 @@ impl Document {
         for y in region.from.y..region.to.y {
             let pos = Pos::new(region.from.x, y);
             let width = 1 + region.to.x - region.from.x;
-            _ = self.text.drain(self.get_byte_offset(pos, width as usize));
+            let deleted = self
+                .text
+                .drain(self.get_byte_offset(pos, width as usize))
+                .as_str()
+                .width();
+            self.markers.delete(y as usize, pos.x as usize, deleted);
         }
         // _ = self.text.drain(self.get_byte_offset(pos, width));
     }
 
-    pub(crate) fn find(&self, cursor: Pos, needle: String) -> Option<usize> {
+    pub(crate) fn find(&self, cursor: Pos, needle: &str) -> Option<usize> {
         let (_, y) = (cursor.x, cursor.y);
         let line_offset = self.byte_offset(Pos::new(0, y));
         let text = &self.text[line_offset..];
@@ -102,7 +103,38 @@ impl Document {
         let end = text.bytes().take_while(|b| *b != b'\n').count();
         let line = &text[..end];
 
-        line.find(&needle)
+        line.find(needle)
+    }
+
+    // The line at `y`, without the newline
+    fn line(&self, y: i32) -> &str {
+        self.text.split('\n').nth(y as usize).unwrap_or_default()
+    }
+
+    /// The closest position to `pos` that is on a line of the document,
+    /// no further than the end of that line. Below the last line is the end of the document.
+    pub(crate) fn clamp(&self, pos: Pos) -> Pos {
+        let last = self.text.split('\n').count() as i32 - 1;
+        let y = pos.y.max(0);
+        if y > last {
+            return Pos::new(self.line(last).width() as i32, last);
+        }
+
+        let width = self.line(y).width() as i32;
+        Pos::new(pos.x.clamp(0, width), y)
+    }
+
+    /// Where the anchor is, given the cursor
+    pub(crate) fn anchor(&self, cursor: Pos, anchor: Anchor) -> Pos {
+        let cursor = self.clamp(cursor);
+        let line = self.line(cursor.y);
+        let x = match anchor {
+            Anchor::End => return self.clamp(Pos::new(0, i32::MAX)),
+            Anchor::LineStart => 0,
+            Anchor::LineEnd => line.width(),
+            Anchor::Indent => line[..line.len() - line.trim_start().len()].width(),
+        };
+        Pos::new(x as i32, cursor.y)
     }
 
     pub(crate) fn clear(&mut self) {
@@ -139,6 +171,25 @@ abcdefg";
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn clamp_to_lines() {
+        let doc = Document::new("fn main() {\n    🦜\n}");
+        assert_eq!(doc.clamp(Pos::new(-3, -1)), Pos::new(0, 0));
+        assert_eq!(doc.clamp(Pos::new(20, 0)), Pos::new(11, 0));
+        assert_eq!(doc.clamp(Pos::new(20, 1)), Pos::new(6, 1));
+        assert_eq!(doc.clamp(Pos::new(0, 9)), Pos::new(1, 2));
+    }
+
+    #[test]
+    fn anchors() {
+        let doc = Document::new("fn main() {\n    let x = 1;\n}");
+        let cursor = Pos::new(7, 1);
+        assert_eq!(doc.anchor(cursor, Anchor::LineStart), Pos::new(0, 1));
+        assert_eq!(doc.anchor(cursor, Anchor::LineEnd), Pos::new(14, 1));
+        assert_eq!(doc.anchor(cursor, Anchor::Indent), Pos::new(4, 1));
+        assert_eq!(doc.anchor(cursor, Anchor::End), Pos::new(1, 2));
+    }
+
     #[test]
     fn insert_offsets_marker() {
         static NEWLINES: usize = 4;
@@ -5,21 +5,33 @@ use anathema::component::*;
 use anathema::default_widgets::{Canvas, CanvasBuffer};
 use anathema::geometry::{LocalPos, Pos, Region, Size};
 use anathema::widgets::query::Elements;
+use parser::OnFinish;
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::audio::AudioShell;
 use crate::document::Document;
-use crate::instructions::Instruction;
+use crate::error::{Error, Result};
+use crate::instructions::{Instruction, Located};
+use crate::location::Location;
 use crate::markers::generate;
+use crate::options::Options;
 use crate::random::Random;
 use crate::syntax::{Highlighter, InactiveScratch};
 use crate::textbuffer::TextBuffer;
 
-enum RenderAction {
+pub(crate) enum RenderAction {
     Render,
     Skip,
 }
 
+// Each speed step scales the frame time by this much,
+// up to `MAX_SPEED_STEPS` in either direction
+const SPEED_STEP: f64 = 1.25;
+const MAX_SPEED_STEPS: i32 = 10;
+
+// The number of snapshots to keep for rewinding, the oldest one is dropped first
+const MAX_SNAPSHOTS: usize = 256;
+
 #[derive(Debug, State, Default)]
 pub struct DocState {
     screen_cursor_x: Value<i32>,
@@ -34,14 +46,17 @@ pub struct DocState {
     debug: Value<String>,
     show_line_numbers: Value<bool>,
     popup: Value<String>,
+    paused: Value<bool>,
+    speed: Value<String>,
+    hint: Value<String>,
 }
 
 // -----------------------------------------------------------------------------
 //   - Visual rang -
 // -----------------------------------------------------------------------------
-#[derive(Debug)]
-struct VisualRange {
-    region: Region,
+#[derive(Debug, Clone)]
+pub(crate) struct VisualRange {
+    pub(crate) region: Region,
 }
 
 impl VisualRange {
@@ -52,7 +67,7 @@ impl VisualRange {
     }
 }
 
-trait OptVisualRange {
+pub(crate) trait OptVisualRange {
     fn contains(&self, pos: Pos) -> bool;
 }
 
@@ -66,30 +81,72 @@ impl OptVisualRange for Option<VisualRange> {
 }
 
 // -----------------------------------------------------------------------------
-//   - Virtual editor -
+//   - Snapshot -
 // -----------------------------------------------------------------------------
-pub struct Editor {
+// Everything needed to restore the editor to the way it was right before
+// an instruction that changes the document
+struct Snapshot {
     doc: Document,
     cursor: Pos,
     offset: Pos,
     selected_range: Option<VisualRange>,
+    instructions: VecDeque<Located>,
+    frame_time: Duration,
+    line_pause: Duration,
+    jitter: Duration,
+    extension: String,
+    theme: String,
+    title: String,
+    popup: String,
+    show_line_numbers: bool,
+}
+
+// -----------------------------------------------------------------------------
+//   - Virtual editor -
+// -----------------------------------------------------------------------------
+pub(crate) struct Editor {
+    pub(crate) doc: Document,
+    pub(crate) cursor: Pos,
+    pub(crate) offset: Pos,
+    pub(crate) selected_range: Option<VisualRange>,
     frame_time: Duration,
     current_time: Duration,
-    instructions: VecDeque<Instruction>,
+    pub(crate) paused: bool,
+    waiting_for_key: bool,
+    pub(crate) hint: String,
+    // Headless editors never wait for a key press
+    interactive: bool,
+    // Treat a `find` that doesn't match as an error rather than skipping it
+    strict: bool,
+    speed_steps: i32,
+    instructions: VecDeque<Located>,
+    // The whole script and the frame time it started with, for looping
+    script: Vec<Located>,
+    script_frame_time: Duration,
+    on_finish: OnFinish,
+    on_finish_override: Option<OnFinish>,
+    // Set once the script is done and the editor should exit
+    exit: bool,
+    // Set when the playback was stopped by an error
+    halted: bool,
+    snapshots: VecDeque<Snapshot>,
     type_buffer: TextBuffer,
-    highlighter: Highlighter,
     rand: Random,
-    buffer: CanvasBuffer,
-    lines: InactiveScratch,
+    seed_override: Option<u64>,
+    // Chapter or marker to fast forward to before the first instruction is applied
+    seek_to: Option<String>,
     line_pause: Duration,
-    extension: String,
-    jitter: u64,
-    theme: String,
+    pub(crate) extension: String,
+    jitter: Duration,
+    pub(crate) theme: String,
     audio: AudioShell,
+    pub(crate) title: String,
+    pub(crate) popup: String,
+    pub(crate) show_line_numbers: bool,
 }
 
 impl Editor {
-    pub fn new(instructions: Vec<Instruction>, highlighter: Highlighter, frame_time: Duration) -> Self {
+    pub fn new(instructions: Vec<Located>, frame_time: Duration, options: Options) -> Self {
         Self {
             doc: Document::new(String::new()),
             cursor: Pos::ZERO,
@@ -97,26 +154,290 @@ impl Editor {
             selected_range: None,
             frame_time,
             current_time: Duration::ZERO,
+            paused: false,
+            waiting_for_key: false,
+            hint: String::new(),
+            interactive: true,
+            strict: false,
+            speed_steps: 0,
+            script: instructions.clone(),
+            script_frame_time: frame_time,
             instructions: instructions.into(),
+            on_finish: OnFinish::Hold,
+            on_finish_override: options.on_finish,
+            exit: false,
+            halted: false,
+            snapshots: VecDeque::new(),
             type_buffer: TextBuffer::new(),
-            highlighter,
-            rand: Random::new(),
-            buffer: CanvasBuffer::default(),
-            lines: InactiveScratch::new(),
+            rand: options.seed.map(Random::seeded).unwrap_or_else(Random::new),
+            seed_override: options.seed,
+            seek_to: options.from,
             line_pause: Duration::ZERO,
             extension: "txt".into(),
-            jitter: 20,
+            jitter: Duration::from_millis(20),
             theme: String::from("togglebit"),
             audio: AudioShell::new(),
+            title: String::new(),
+            popup: String::new(),
+            show_line_numbers: false,
         }
     }
 
-    fn error(&mut self, state: &mut DocState, msg: impl Into<String>) {
+    /// Create an editor that never touches an audio device.
+    pub fn headless(instructions: Vec<Located>, frame_time: Duration, options: Options) -> Self {
+        Self {
+            audio: AudioShell::disabled(),
+            interactive: false,
+            ..Self::new(instructions, frame_time, options)
+        }
+    }
+
+    /// Create a headless editor that reports every problem it runs into,
+    /// for checking a script without playing it.
+    pub fn strict(instructions: Vec<Located>, options: Options) -> Self {
+        Self {
+            strict: true,
+            ..Self::headless(instructions, Duration::ZERO, options)
+        }
+    }
+
+    /// True once every instruction has been applied and there is nothing
+    /// left to type.
+    pub fn is_done(&self) -> bool {
+        self.instructions.is_empty() && self.type_buffer.is_empty()
+    }
+
+    fn next_instruction(&self) -> Option<&Instruction> {
+        self.instructions.front().map(|located| &located.instruction)
+    }
+
+    /// The time to wait before the next instruction is applied
+    pub fn delay(&self) -> Duration {
+        self.current_time
+    }
+
+    /// Drop all remaining instructions.
+    /// A halted editor ignores the `onfinish` behaviour.
+    pub fn halt(&mut self) {
         self.instructions.clear();
-        state.error.set(msg.into());
+        self.halted = true;
+    }
+
+    /// True once the script is done and the playback should return to the shell
+    pub fn should_exit(&self) -> bool {
+        self.exit
+    }
+
+    // Start over from the beginning of the script with a cleared document.
+    // The playback controls and the loaded audio are kept as they are.
+    fn restart(&mut self) {
+        let options = Options {
+            seed: self.seed_override,
+            on_finish: self.on_finish_override,
+            from: None,
+        };
+        let editor = Self::new(self.script.clone(), self.script_frame_time, options);
+        let old = std::mem::replace(self, editor);
+
+        self.audio = old.audio;
+        self.interactive = old.interactive;
+        self.paused = old.paused;
+        self.speed_steps = old.speed_steps;
     }
 
-    fn apply(&mut self, state: &mut DocState) -> RenderAction {
+    /// Count down the current delay and apply the next instruction once it
+    /// has run out.
+    pub fn update(&mut self, dt: Duration) -> Result<RenderAction> {
+        if self.paused || self.waiting_for_key {
+            return Ok(RenderAction::Skip);
+        }
+
+        self.current_time = self.current_time.saturating_sub(dt);
+
+        if self.current_time > Duration::ZERO {
+            return Ok(RenderAction::Skip);
+        }
+
+        if self.is_done() && !self.halted {
+            match self.on_finish_override.unwrap_or(self.on_finish) {
+                OnFinish::Hold => {}
+                OnFinish::Loop => {
+                    self.restart();
+                    return Ok(RenderAction::Render);
+                }
+                OnFinish::Exit => self.exit = true,
+            }
+        }
+
+        self.advance()
+    }
+
+    /// Apply the next instruction without waiting.
+    /// The time until the following instruction is available via [`Editor::delay`].
+    pub fn advance(&mut self) -> Result<RenderAction> {
+        if let Some(name) = self.seek_to.take() {
+            self.seek(Some(&name))?;
+            return Ok(RenderAction::Render);
+        }
+
+        // The delay is picked after the instruction is applied so a `seed` or
+        // `speed` instruction applies to its own frame.
+        // Waits and line pauses set the delay themselves.
+        self.current_time = Duration::ZERO;
+        let action = self.apply()?;
+        if self.current_time == Duration::ZERO {
+            let frame_time = self.frame_time + Duration::from_nanos(self.rand.next(self.jitter.as_nanos() as u64));
+            self.current_time = frame_time.div_f64(self.speed());
+        }
+        Ok(action)
+    }
+
+    /// Apply the next instruction in its entirety:
+    /// any text it loads is typed out at once, without audio.
+    /// If the editor is in the middle of typing, the rest of the text is typed out instead.
+    pub fn step(&mut self) -> Result<()> {
+        self.audio.set_muted(true);
+        let res = self.step_inner();
+        self.audio.set_muted(false);
+        self.current_time = Duration::ZERO;
+        res
+    }
+
+    fn step_inner(&mut self) -> Result<()> {
+        loop {
+            self.apply()?;
+
+            // Markers are added once the text is typed out, and are
+            // considered part of the instruction that produced them
+            let adding_markers = matches!(self.next_instruction(), Some(Instruction::AddMarkers { .. }));
+            if self.type_buffer.is_empty() && !adding_markers {
+                break Ok(());
+            }
+        }
+    }
+
+    /// Resume the playback if it is waiting for a key press.
+    /// Returns false if the playback wasn't waiting.
+    pub fn key_pressed(&mut self) -> bool {
+        if !self.waiting_for_key {
+            return false;
+        }
+
+        self.waiting_for_key = false;
+        self.hint.clear();
+        true
+    }
+
+    /// Fast forward to the chapter, or marker, with the given name,
+    /// or the next chapter if no name is given.
+    ///
+    /// Every instruction up to that point is applied instantly:
+    /// text is typed out at once, waits are skipped and audio is muted.
+    pub fn seek(&mut self, name: Option<&str>) -> Result<()> {
+        let interactive = self.interactive;
+        self.interactive = false;
+        self.audio.set_muted(true);
+
+        let res = self.seek_inner(name);
+
+        self.interactive = interactive;
+        self.audio.set_muted(false);
+        self.current_time = Duration::ZERO;
+        res
+    }
+
+    fn seek_inner(&mut self, name: Option<&str>) -> Result<()> {
+        loop {
+            if self.type_buffer.is_empty() {
+                match self.next_instruction() {
+                    Some(Instruction::Chapter(chapter)) if name.is_none_or(|name| name == chapter) => {
+                        self.instructions.pop_front();
+                        break Ok(());
+                    }
+                    Some(_) => {}
+                    None => match name {
+                        Some(name) => break Err(Error::MissingChapter(name.into())),
+                        None => break Ok(()),
+                    },
+                }
+            }
+
+            self.apply()?;
+
+            let adding_markers = matches!(self.next_instruction(), Some(Instruction::AddMarkers { .. }));
+            if let Some(name) = name
+                && self.type_buffer.is_empty()
+                && !adding_markers
+                && self.doc.lookup_marker(name).is_some()
+            {
+                break Ok(());
+            }
+        }
+    }
+
+    /// Restore the editor to the way it was before the most recent change
+    /// to the document. Returns false if there is nothing to rewind.
+    pub fn rewind(&mut self) -> bool {
+        let Some(snapshot) = self.snapshots.pop_back() else { return false };
+
+        self.doc = snapshot.doc;
+        self.cursor = snapshot.cursor;
+        self.offset = snapshot.offset;
+        self.selected_range = snapshot.selected_range;
+        self.instructions = snapshot.instructions;
+        self.frame_time = snapshot.frame_time;
+        self.line_pause = snapshot.line_pause;
+        self.jitter = snapshot.jitter;
+        self.extension = snapshot.extension;
+        self.theme = snapshot.theme;
+        self.title = snapshot.title;
+        self.popup = snapshot.popup;
+        self.show_line_numbers = snapshot.show_line_numbers;
+
+        self.type_buffer.clear();
+        self.waiting_for_key = false;
+        self.hint.clear();
+        self.current_time = Duration::ZERO;
+        true
+    }
+
+    fn snapshot(&mut self) {
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(Snapshot {
+            doc: self.doc.clone(),
+            cursor: self.cursor,
+            offset: self.offset,
+            selected_range: self.selected_range.clone(),
+            instructions: self.instructions.clone(),
+            frame_time: self.frame_time,
+            line_pause: self.line_pause,
+            jitter: self.jitter,
+            extension: self.extension.clone(),
+            theme: self.theme.clone(),
+            title: self.title.clone(),
+            popup: self.popup.clone(),
+            show_line_numbers: self.show_line_numbers,
+        });
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Speed up (or slow down with a negative `steps`) the frame time
+    pub fn change_speed(&mut self, steps: i32) {
+        self.speed_steps = (self.speed_steps + steps).clamp(-MAX_SPEED_STEPS, MAX_SPEED_STEPS);
+    }
+
+    /// The multiplier applied to the speed of the script
+    pub fn speed(&self) -> f64 {
+        SPEED_STEP.powi(self.speed_steps)
+    }
+
+    fn apply(&mut self) -> Result<RenderAction> {
         // If we have something to type then do that.
         // otherwise load the next instruction
         if let Some(s) = self.type_buffer.next() {
@@ -135,100 +456,132 @@ impl Editor {
                 self.cursor.x += s.width() as i32;
             }
 
-            return RenderAction::Render;
+            return Ok(RenderAction::Render);
+        }
+
+        // Only keep snapshots when there is someone around to rewind,
+        // this also means nothing is recorded while seeking
+        let changes_document = matches!(
+            self.next_instruction(),
+            Some(Instruction::LoadTypeBuffer(_) | Instruction::Insert(_) | Instruction::Delete | Instruction::Clear)
+        );
+        if self.interactive && changes_document {
+            self.snapshot();
         }
 
-        let instruction = self.instructions.pop_front();
+        let Some(Located { instruction, location }) = self.instructions.pop_front() else {
+            return Ok(RenderAction::Skip);
+        };
+        self.apply_instruction(instruction, &location)
+            .map_err(|error| error.at(location))
+    }
+
+    fn apply_instruction(&mut self, instruction: Instruction, location: &Option<Location>) -> Result<RenderAction> {
         match instruction {
-            None => return RenderAction::Skip,
-            Some(instruction) => {
-                match instruction {
-                    Instruction::LoadTypeBuffer(content) => {
-                        // Make markers and all that what what
-                        let (content, markers) = generate(content);
-                        self.type_buffer.push(content);
-
-                        if let Some(markers) = markers {
-                            self.instructions.push_front(Instruction::AddMarkers {
-                                row: self.cursor.y as usize,
-                                markers,
-                            });
-                        }
-                    }
-                    Instruction::Insert(content) => {
-                        let (content, markers) = generate(content);
-                        self.cursor.x = 0;
-                        self.doc.insert_str(self.cursor, &content);
-                        if let Some(markers) = markers {
-                            self.instructions.push_front(Instruction::AddMarkers {
-                                row: self.cursor.y as usize,
-                                markers,
-                            });
-                        }
-                    }
-                    Instruction::AddMarkers { row, markers } => self.doc.add_markers(row, markers),
-                    Instruction::Jump(pos) => {
-                        self.cursor += pos;
-                        // Don't move the cursor past zero
-                        self.cursor.x = self.cursor.x.max(0);
-                        self.cursor.y = self.cursor.y.max(0);
-                    }
-                    Instruction::JumpToMarker(name) => {
-                        let Some(row) = self.doc.lookup_marker(&name).map(|m| m.row) else {
-                            self.error(state, format!("marker \"{name}\" does not exist"));
-                            return RenderAction::Render;
-                        };
-                        self.cursor.y = row as i32;
-                        self.cursor.x = 0;
-                    }
-                    Instruction::Select(size) if size == Size::ZERO => return RenderAction::Render,
-                    Instruction::Select(size) => {
-                        let visual_range = VisualRange::new(self.cursor, size);
-                        self.cursor = visual_range.region.to - Pos::new(1, 1);
-                        self.selected_range = Some(visual_range);
-                    }
-                    Instruction::Delete => match self.selected_range.take() {
-                        Some(range) => {
-                            self.cursor = range.region.from;
-                            self.doc.delete(range.region);
-                        }
-                        None => self.doc.delete(Region::from((self.cursor, Size::new(1, 1)))),
-                    },
-                    Instruction::Wait(dur) => self.current_time = dur,
-                    Instruction::Speed(dur) => self.frame_time = dur,
-                    Instruction::FindInCurrentLine(text) => {
-                        let Some(x) = self.doc.find(self.cursor, text) else { return RenderAction::Render };
-                        self.cursor.x = x as i32;
-                    }
-                    Instruction::LinePause(duration) => self.line_pause = duration,
-                    Instruction::SetTitle(title) => state.title.set(title),
-                    Instruction::SetJitter(jitter) => self.jitter = jitter,
-                    Instruction::ShowLineNumbers(show) => state.show_line_numbers.set(show),
-                    Instruction::Clear => {
-                        self.doc.clear();
-                        self.offset = Pos::ZERO;
-                        self.cursor = Pos::ZERO;
-                    }
-                    Instruction::SetExtension(ext) => self.extension = ext,
-                    Instruction::SetTheme(theme) => self.theme = theme,
-                    Instruction::LoadAudio(path) => {
-                        if let Err(e) = self.audio.load(path) {
-                            self.error(state, e.to_string());
-                        }
-                    }
-                    Instruction::Popup(message) => state.popup.set(message),
-                    Instruction::ClosePopup => state.popup.set(String::new()),
+            Instruction::LoadTypeBuffer(content) => {
+                // Make markers and all that what what
+                let (content, markers) = generate(content);
+                self.type_buffer.push(content);
+
+                if let Some(markers) = markers {
+                    self.instructions.push_front(Located {
+                        instruction: Instruction::AddMarkers {
+                            row: self.cursor.y as usize,
+                            col: self.cursor.x as usize,
+                            markers,
+                        },
+                        location: location.clone(),
+                    });
+                }
+            }
+            Instruction::Insert(content) => {
+                let (content, markers) = generate(content);
+                self.cursor.x = 0;
+                self.doc.insert_str(self.cursor, &content);
+                if let Some(markers) = markers {
+                    self.instructions.push_front(Located {
+                        instruction: Instruction::AddMarkers {
+                            row: self.cursor.y as usize,
+                            col: self.cursor.x as usize,
+                            markers,
+                        },
+                        location: location.clone(),
+                    });
+                }
+            }
+            Instruction::AddMarkers { row, col, markers } => self.doc.add_markers(row, col, markers),
+            Instruction::Jump(pos) => self.cursor = self.doc.clamp(self.cursor + pos),
+            Instruction::JumpTo(pos) => self.cursor = self.doc.clamp(pos),
+            Instruction::JumpToMarker { name, offset } => {
+                let Some(marker) = self.doc.lookup_marker(&name) else {
+                    return Err(Error::MissingMarker(name));
+                };
+                self.cursor = self.doc.clamp(Pos::from(marker) + offset);
+            }
+            Instruction::JumpToAnchor(anchor) => self.cursor = self.doc.anchor(self.cursor, anchor),
+            Instruction::Select(size) if size == Size::ZERO => return Ok(RenderAction::Render),
+            Instruction::Select(size) => {
+                let visual_range = VisualRange::new(self.cursor, size);
+                self.cursor = visual_range.region.to - Pos::new(1, 1);
+                self.selected_range = Some(visual_range);
+            }
+            Instruction::Delete => match self.selected_range.take() {
+                Some(range) => {
+                    self.cursor = range.region.from;
+                    self.doc.delete(range.region);
+                }
+                None => self.doc.delete(Region::from((self.cursor, Size::new(1, 1)))),
+            },
+            Instruction::Wait(dur) => self.current_time = dur,
+            Instruction::Speed(dur) => self.frame_time = dur,
+            Instruction::FindInCurrentLine(text) => {
+                let Some(x) = self.doc.find(self.cursor, &text) else {
+                    return match self.strict {
+                        true => Err(Error::MissingNeedle(text)),
+                        false => Ok(RenderAction::Render),
+                    };
+                };
+                self.cursor.x = x as i32;
+            }
+            Instruction::LinePause(duration) => self.line_pause = duration,
+            Instruction::SetTitle(title) => self.title = title,
+            Instruction::SetJitter(jitter) => self.jitter = jitter,
+            Instruction::Seed(seed) => {
+                if self.seed_override.is_none() {
+                    self.rand = Random::seeded(seed);
+                }
+            }
+            Instruction::ShowLineNumbers(show) => self.show_line_numbers = show,
+            Instruction::Clear => {
+                self.doc.clear();
+                self.offset = Pos::ZERO;
+                self.cursor = Pos::ZERO;
+            }
+            Instruction::SetExtension(ext) => self.extension = ext,
+            Instruction::SetTheme(theme) => self.theme = theme,
+            Instruction::LoadAudio(path) => self.audio.load(path)?,
+            Instruction::Popup(message) => self.popup = message,
+            Instruction::ClosePopup => self.popup = String::new(),
+            Instruction::Chapter(_) => {}
+            Instruction::OnFinish(on_finish) => self.on_finish = on_finish,
+            Instruction::WaitForKey(hint) => {
+                if self.interactive {
+                    self.waiting_for_key = true;
+                    self.hint = hint.unwrap_or_default();
                 }
             }
         }
 
-        RenderAction::Render
+        Ok(RenderAction::Render)
     }
 
-    fn update_cursor(&mut self, size: Size, state: &mut DocState) {
+    /// Offset the view so the cursor stays visible inside a canvas of the given size
+    pub fn scroll(&mut self, size: Size) {
         static PADDING: i32 = 7;
 
-        let height = size.height as i32 - 1 - PADDING;
+        // A canvas shorter than the padding would otherwise scroll back and forth on every frame
+        let padding = PADDING.min((size.height as i32 - 1) / 2);
+        let height = size.height as i32 - 1 - padding;
         let width = size.width as i32 - 1;
 
         let y = self.cursor.y + self.offset.y;
@@ -244,13 +597,82 @@ impl Editor {
         } else if x < 0 {
             self.offset.x -= self.cursor.x + self.offset.x;
         }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//   - Editor view -
+// -----------------------------------------------------------------------------
+pub struct EditorView {
+    editor: Editor,
+    highlighter: Highlighter,
+    buffer: CanvasBuffer,
+    lines: InactiveScratch,
+}
+
+impl EditorView {
+    pub fn new(instructions: Vec<Located>, highlighter: Highlighter, frame_time: Duration, options: Options) -> Self {
+        Self {
+            editor: Editor::new(instructions, frame_time, options),
+            highlighter,
+            buffer: CanvasBuffer::default(),
+            lines: InactiveScratch::new(),
+        }
+    }
+
+    fn error(&mut self, state: &mut DocState, msg: impl Into<String>) {
+        self.editor.halt();
+        state.error.set(msg.into());
+    }
+
+    fn update_state(&mut self, size: Size, state: &mut DocState) {
+        let editor = &mut self.editor;
+        editor.scroll(size);
+
+        state.screen_cursor_x.set(editor.cursor.x + editor.offset.x);
+        state.screen_cursor_y.set(editor.cursor.y + editor.offset.y);
+        state.cursor_x.set(editor.cursor.x);
+        state.cursor_y.set(editor.cursor.y);
+        state.offset_x.set(editor.offset.x);
+        state.offset_y.set(editor.offset.y);
+
+        if *state.title.to_ref() != editor.title {
+            state.title.set(editor.title.clone());
+        }
+
+        if *state.popup.to_ref() != editor.popup {
+            state.popup.set(editor.popup.clone());
+        }
+
+        if state.show_line_numbers.copy_value() != editor.show_line_numbers {
+            state.show_line_numbers.set(editor.show_line_numbers);
+        }
+
+        if *state.hint.to_ref() != editor.hint {
+            state.hint.set(editor.hint.clone());
+        }
+
+        if state.paused.copy_value() != editor.paused {
+            state.paused.set(editor.paused);
+        }
+
+        // Only show the speed once it differs from the speed of the script
+        let speed = match editor.speed_steps {
+            0 => String::new(),
+            _ => format!("{:.2}x", editor.speed()),
+        };
+        if *state.speed.to_ref() != speed {
+            state.speed.set(speed);
+        }
+    }
+
+    fn refresh(&mut self, state: &mut DocState, mut children: Children<'_, '_>) {
+        let Some(size) = children.elements().by_tag("canvas").first(|el, _| el.size()) else {
+            return;
+        };
 
-        state.screen_cursor_x.set(self.cursor.x + self.offset.x);
-        state.screen_cursor_y.set(self.cursor.y + self.offset.y);
-        state.cursor_x.set(self.cursor.x);
-        state.cursor_y.set(self.cursor.y);
-        state.offset_x.set(self.offset.x);
-        state.offset_y.set(self.offset.y);
+        self.update_state(size, state);
+        self.draw(children.elements(), state);
     }
 
     fn draw(&mut self, mut elements: Elements<'_, '_, '_>, state: &mut DocState) {
@@ -258,17 +680,19 @@ impl Editor {
             let canvas = el.to::<Canvas>();
             canvas.clear();
 
-            let mut y = self.offset.y;
+            let editor = &self.editor;
+            let mut y = editor.offset.y;
 
             // re-highlight the content
-            let scratch = unsafe { self.lines.activate(self.doc.text()) };
+            let scratch = unsafe { self.lines.activate(editor.doc.text()) };
             let res = scratch.with(|lines, code| {
-                self.highlighter.highlight(&self.theme, code, &self.extension, lines)?;
+                self.highlighter
+                    .highlight(&editor.theme, code, &editor.extension, lines)?;
 
-                let skip = (y < 0).then_some(y.abs() as usize).unwrap_or(0);
+                let skip = if y < 0 { y.unsigned_abs() as usize } else { 0 };
                 y = 0;
                 for spans in lines.iter().skip(skip) {
-                    let mut x = self.offset.x;
+                    let mut x = editor.offset.x;
                     for span in spans {
                         for c in span.src.chars() {
                             if x >= 0 {
@@ -277,7 +701,7 @@ impl Editor {
                                 // if we have a selected range
                                 // then set the background of the style to red
                                 // but only if the pos is inside the selected range
-                                if self.selected_range.contains(pos.into()) {
+                                if editor.selected_range.contains(pos.into()) {
                                     style.bg = Some(Color::Red);
                                 }
                                 canvas.put(c, style, pos);
@@ -299,26 +723,59 @@ impl Editor {
     }
 }
 
-impl Component for Editor {
+impl Component for EditorView {
     type Message = Instruction;
     type State = DocState;
 
-    fn on_key(&mut self, key: KeyEvent, _: &mut Self::State, _: Children<'_, '_>, _: Context<'_, '_, Self::State>) {
+    fn on_key(
+        &mut self,
+        key: KeyEvent,
+        state: &mut Self::State,
+        children: Children<'_, '_>,
+        mut context: Context<'_, '_, Self::State>,
+    ) {
+        if key.state == KeyState::Release {
+            return;
+        }
+
+        // Any key but `q` continues a `pause` instruction
+        if key.code != KeyCode::Char('q') && self.editor.key_pressed() {
+            return self.refresh(state, children);
+        }
+
         match key.code {
-            // KeyCode::Char('h') => self.instructions.push_back(Instruction::Jump(Pos::new(-1, 0))),
-            // KeyCode::Char('j') => self.instructions.push_back(Instruction::Jump(Pos::new(0, 1))),
-            // KeyCode::Char('k') => self.instructions.push_back(Instruction::Jump(Pos::new(0, -1))),
-            // KeyCode::Char('l') => self.instructions.push_back(Instruction::Jump(Pos::new(1, 0))),
-            // KeyCode::Char('d') => self.instructions.push_back(Instruction::Jump(Pos::new(0, 9))),
-            _ => {}
+            KeyCode::Char(' ') => self.editor.toggle_pause(),
+            // Single stepping pauses the playback
+            KeyCode::Char('n') => {
+                self.editor.paused = true;
+                if let Err(e) = self.editor.step() {
+                    self.error(state, e.to_string());
+                }
+            }
+            // Rewinding pauses the playback so there is time to look at it
+            KeyCode::Char('b') => {
+                self.editor.paused = true;
+                self.editor.rewind();
+            }
+            KeyCode::Char('f') => {
+                if let Err(e) = self.editor.seek(None) {
+                    self.error(state, e.to_string());
+                }
+            }
+            KeyCode::Char('+') => self.editor.change_speed(1),
+            KeyCode::Char('-') => self.editor.change_speed(-1),
+            KeyCode::Char('q') => return context.stop_runtime(),
+            _ => return,
         }
+
+        self.refresh(state, children);
     }
 
     fn on_tick(
         &mut self,
         state: &mut Self::State,
         mut children: Children<'_, '_>,
-        _: Context<'_, '_, Self::State>,
+        mut context: Context<'_, '_, Self::State>,
         dt: Duration,
     ) {
         let Some(size) = children.elements().by_tag("canvas").first(|el, _| el.size()) else {
@@ -327,16 +784,20 @@ impl Component for Editor {
 
         state.height.set(size.height);
 
-        self.current_time = self.current_time.saturating_sub(dt);
+        let action = match self.editor.update(dt) {
+            Ok(action) => action,
+            Err(e) => {
+                self.error(state, e.to_string());
+                RenderAction::Render
+            }
+        };
 
-        if self.current_time > Duration::ZERO {
-            return;
+        if let RenderAction::Render = action {
+            self.refresh(state, children);
         }
 
-        self.current_time = self.frame_time + Duration::from_millis(self.rand.next(self.jitter));
-        if let RenderAction::Render = self.apply(state) {
-            self.update_cursor(size, state);
-            self.draw(children.elements(), state);
+        if self.editor.should_exit() {
+            context.stop_runtime();
         }
     }
 
@@ -356,3 +817,155 @@ impl Component for Editor {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn located(instructions: Vec<Instruction>) -> Vec<Located> {
+        instructions.into_iter().map(Located::from).collect()
+    }
+
+    fn editor(instructions: Vec<Instruction>) -> Editor {
+        Editor::headless(located(instructions), Duration::from_millis(10), Options::default())
+    }
+
+    #[test]
+    fn step_types_the_whole_instruction() {
+        let mut editor = editor(vec![
+            Instruction::LoadTypeBuffer("// @a\nhello".into()),
+            Instruction::LoadTypeBuffer(" world".into()),
+        ]);
+
+        editor.step().unwrap();
+        assert_eq!(editor.doc.text(), "hello");
+        assert!(editor.doc.lookup_marker("a").is_some());
+
+        // Finish typing the rest of the current instruction
+        editor.advance().unwrap();
+        editor.advance().unwrap();
+        editor.step().unwrap();
+        assert_eq!(editor.doc.text(), "hello world");
+        assert!(editor.is_done());
+    }
+
+    #[test]
+    fn wait_for_key() {
+        let mut editor = Editor::new(
+            located(vec![Instruction::WaitForKey(Some("go".into())), Instruction::Clear]),
+            Duration::ZERO,
+            Options::default(),
+        );
+
+        editor.update(Duration::ZERO).unwrap();
+        assert_eq!(editor.hint, "go");
+
+        let RenderAction::Skip = editor.update(Duration::from_secs(10)).unwrap() else { panic!() };
+        assert!(!editor.is_done());
+
+        assert!(editor.key_pressed());
+        assert!(editor.hint.is_empty());
+        editor.update(Duration::from_secs(1)).unwrap();
+        assert!(editor.is_done());
+    }
+
+    #[test]
+    fn speed_scales_frame_time() {
+        let mut editor = editor(vec![Instruction::SetJitter(Duration::ZERO), Instruction::Clear]);
+        editor.advance().unwrap();
+
+        editor.change_speed(-1);
+        editor.advance().unwrap();
+        assert_eq!(editor.delay(), Duration::from_micros(12500));
+
+        editor.change_speed(100);
+        assert_eq!(editor.speed(), SPEED_STEP.powi(MAX_SPEED_STEPS));
+    }
+
+    fn finish(on_finish: OnFinish) -> Editor {
+        let options = Options {
+            on_finish: Some(on_finish),
+            ..Default::default()
+        };
+        let mut editor = Editor::new(
+            located(vec![Instruction::LoadTypeBuffer("a".into())]),
+            Duration::ZERO,
+            options,
+        );
+        while !editor.is_done() {
+            editor.update(Duration::from_secs(1)).unwrap();
+        }
+        editor.update(Duration::from_secs(1)).unwrap();
+        editor
+    }
+
+    #[test]
+    fn on_finish() {
+        let editor = finish(OnFinish::Hold);
+        assert_eq!(editor.doc.text(), "a");
+        assert!(!editor.should_exit());
+
+        let editor = finish(OnFinish::Exit);
+        assert_eq!(editor.doc.text(), "a");
+        assert!(editor.should_exit());
+
+        let editor = finish(OnFinish::Loop);
+        assert_eq!(editor.doc.text(), "");
+        assert!(!editor.is_done());
+        assert!(!editor.should_exit());
+    }
+
+    #[test]
+    fn on_finish_instruction() {
+        let mut editor = Editor::new(
+            located(vec![Instruction::OnFinish(OnFinish::Exit), Instruction::Clear]),
+            Duration::ZERO,
+            Options::default(),
+        );
+        while !editor.is_done() {
+            editor.update(Duration::from_secs(1)).unwrap();
+        }
+        editor.update(Duration::from_secs(1)).unwrap();
+        assert!(editor.should_exit());
+
+        // Errors are left on screen
+        let mut editor = finish(OnFinish::Loop);
+        editor.halt();
+        editor.update(Duration::from_secs(1)).unwrap();
+        assert!(editor.is_done());
+    }
+
+    #[test]
+    fn rewind_to_previous_change() {
+        let mut editor = Editor::new(
+            located(vec![
+                Instruction::LoadTypeBuffer("ab".into()),
+                Instruction::SetTitle("title".into()),
+                Instruction::LoadTypeBuffer("cd".into()),
+            ]),
+            Duration::ZERO,
+            Options::default(),
+        );
+
+        while !editor.is_done() {
+            editor.advance().unwrap();
+        }
+        assert_eq!(editor.doc.text(), "abcd");
+
+        assert!(editor.rewind());
+        assert_eq!(editor.doc.text(), "ab");
+        assert_eq!(editor.cursor, Pos::new(2, 0));
+        assert_eq!(editor.title, "title");
+
+        assert!(editor.rewind());
+        assert_eq!(editor.doc.text(), "");
+        assert!(editor.title.is_empty());
+        assert!(!editor.rewind());
+
+        // Playing it again gives the same result
+        while !editor.is_done() {
+            editor.advance().unwrap();
+        }
+        assert_eq!(editor.doc.text(), "abcd");
+    }
+}
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::location::Location;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -7,23 +9,79 @@ pub enum Error {
     Import(PathBuf),
     LoadValue(String),
     FilePath(PathBuf),
+    Io(std::io::Error),
     Anathema(anathema::runtime::Error),
     Syntect(syntect::Error),
     Kira(kira::backend::cpal::Error),
     InvalidTheme(String),
+    MissingMarker(String),
+    // A marker that `goto` can't reach, as its name is taken by an anchor
+    ReservedMarker(String),
+    MissingNeedle(String),
+    MissingChapter(String),
+    MissingMacro(String),
+    Parse(PathBuf, parser::Errors),
+    IncludeCycle(PathBuf),
+    // An error in a script that was included from `file` at `line`
+    Include {
+        file: PathBuf,
+        line: u16,
+        error: Box<Error>,
+    },
+    RecursiveMacro(String),
+    MacroArgs {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
     NoDefaultSound,
+    // An error caused by the instruction at the location
+    At(Location, Box<Error>),
+}
+
+impl Error {
+    // Point the error at the instruction that caused it,
+    // unless it already points somewhere more specific
+    pub(crate) fn at(self, location: Option<Location>) -> Self {
+        match (self, location) {
+            (error @ (Error::At(..) | Error::Include { .. }), _) | (error, None) => error,
+            (error, Some(location)) => Error::At(location, Box::new(error)),
+        }
+    }
 }
 
+// The alternate flag (`{:#}`) is passed on to parse errors to colour them
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Import(path) => write!(f, "failed to load \"{}\"", path.to_str().unwrap_or("<path>")),
             Error::LoadValue(key) => write!(f, "\"{key}\" does not exist"),
             Error::FilePath(path_buf) => write!(f, "file does not exist: {}", path_buf.to_str().unwrap_or("<path>")),
+            Error::Io(error) => write!(f, "{error}"),
             Error::Anathema(error) => write!(f, "{error}"),
             Error::Syntect(error) => write!(f, "{error}"),
             Error::Kira(error) => write!(f, "{error}"),
             Error::InvalidTheme(theme) => write!(f, "no theme named \"{theme}\""),
+            Error::MissingMarker(name) => write!(f, "marker \"{name}\" does not exist"),
+            Error::ReservedMarker(name) => {
+                write!(f, "\"{name}\" can't name a marker, `goto {name}` doesn't go to markers")
+            }
+            Error::MissingNeedle(needle) => write!(f, "\"{needle}\" is not on the cursor line"),
+            Error::MissingChapter(name) => write!(f, "there is no chapter or marker named \"{name}\""),
+            Error::Parse(path, error) if f.alternate() => write!(f, "failed to parse {}{error:#}", path.display()),
+            Error::Parse(path, error) => write!(f, "failed to parse {}{error}", path.display()),
+            Error::IncludeCycle(path) => write!(f, "\"{}\" ends up including itself", path.display()),
+            Error::Include { file, line, error } if f.alternate() => write!(f, "{}:{line}: {error:#}", file.display()),
+            Error::Include { file, line, error } => write!(f, "{}:{line}: {error}", file.display()),
+            Error::MissingMacro(name) => write!(f, "no macro named \"{name}\" has been defined"),
+            Error::RecursiveMacro(name) => write!(f, "macro \"{name}\" calls itself"),
+            Error::MacroArgs { name, expected, found } => {
+                write!(
+                    f,
+                    "macro \"{name}\" takes {expected} argument(s) but {found} were given"
+                )
+            }
+            Error::At(location, error) => location.fmt_message(f, error),
             Error::NoDefaultSound => write!(
                 f,
                 "default sound missing. there has to be a default.mp3 in the root of the sound dir"
@@ -35,6 +93,12 @@ impl std::fmt::Display for Error {
 impl std::error::Error for Error {
 }
 
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
 impl From<anathema::runtime::Error> for Error {
     fn from(e: anathema::runtime::Error) -> Self {
         Self::Anathema(e)
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use anathema::geometry::Pos;
+
+use crate::editor::Editor;
+use crate::error::Result;
+use crate::instructions::Located;
+use crate::markers::Marker;
+use crate::options::Options;
+
+/// The state of the editor once every instruction has been applied.
+#[derive(Debug)]
+pub struct Output {
+    pub text: String,
+    pub cursor: Pos,
+    pub markers: Vec<Marker>,
+    /// How long the playback would have taken in a terminal
+    pub duration: Duration,
+}
+
+/// Play the instructions back without a terminal.
+///
+/// This applies the instructions exactly like [`crate::run`] would,
+/// except nothing is drawn, no audio is played and nothing waits in real time.
+pub fn run_headless(instructions: Vec<Located>, options: Options) -> Result<Output> {
+    let mut editor = Editor::headless(instructions, Duration::from_millis(70), options);
+    let mut duration = Duration::ZERO;
+
+    while !editor.is_done() {
+        editor.advance()?;
+        duration += editor.delay();
+    }
+
+    let output = Output {
+        text: editor.doc.text().to_string(),
+        cursor: editor.cursor,
+        markers: editor.doc.markers.into_iter().collect(),
+        duration,
+    };
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compile;
+
+    fn play(src: &str) -> Output {
+        let instructions = parser::parse(src).unwrap();
+        let instructions = compile(instructions).unwrap();
+        run_headless(instructions, Options::default()).unwrap()
+    }
+
+    #[test]
+    fn type_and_replace() {
+        let output = play(
+            r#"
+type "// @marker
+fn pain() {
+}"
+goto marker
+replace "pain" "main"
+"#,
+        );
+
+        assert_eq!(output.text, "fn main() {\n}");
+        assert_eq!(output.cursor, Pos::new(7, 0));
+        assert_eq!(output.markers.len(), 1);
+        assert_eq!(output.markers[0].name(), "marker");
+        assert_eq!(output.markers[0].row, 0);
+    }
+
+    #[test]
+    fn play_built_script() {
+        let script = parser::Script::new()
+            .type_str("// @marker\nfn pain() {\n}")
+            .goto_marker("marker")
+            .replace_str("pain", "main");
+        let instructions = compile(script.build()).unwrap();
+        let output = run_headless(instructions, Options::default()).unwrap();
+        assert_eq!(output.text, "fn main() {\n}");
+    }
+
+    #[test]
+    fn goto_positions() {
+        let output = play(
+            r#"
+type "// @body
+fn main() {
+    let x = 1;
+}"
+goto line 2 col 9
+type "y"
+goto eol
+type " // one"
+goto body 0 99
+type "!"
+goto 9 0
+type "\n"
+"#,
+        );
+
+        assert_eq!(
+            output.text,
+            "fn main() {!
+    let yx = 1; // one
+}
+"
+        );
+        assert_eq!(output.cursor, Pos::new(0, 3));
+    }
+
+    #[test]
+    fn goto_inline_marker() {
+        let output = play(
+            r#"
+type "fn main() {\n    let x = 1; // @here\n}"
+goto here
+type " // one"
+goto here -1 0
+type "!"
+"#,
+        );
+
+        assert_eq!(output.text, "fn main() {!\n    let x = 1; // one\n}");
+        assert_eq!(output.markers[0].col, 14);
+    }
+
+    #[test]
+    fn goto_past_the_end() {
+        let output = play("type \"fn main() {\\n}\"\ngoto line 1\ngoto line 4294967297 col 4294967297\ntype \"!\"");
+        assert_eq!(output.text, "fn main() {\n}!");
+    }
+
+    #[test]
+    fn edit_before_inline_marker() {
+        let output = play(
+            r#"
+type "fn main() {\n    let x = 1; // @here\n}"
+goto line 2 col 9
+type "mut "
+goto here
+type "!"
+goto line 2 col 5
+select 4 1
+delete
+goto here
+type "?"
+"#,
+        );
+
+        assert_eq!(output.text, "fn main() {\n    mut x = 1;?!\n}");
+        assert_eq!(output.markers[0].col, 14);
+    }
+
+    #[test]
+    fn built_script_errors() {
+        let err = compile(parser::Script::new().type_ident("nope").build()).unwrap_err();
+        assert_eq!(err.to_string(), "<script>: \"nope\" does not exist");
+
+        let instructions = compile(parser::Script::new().goto_marker("nope").build()).unwrap();
+        let err = run_headless(instructions, Options::default()).unwrap_err();
+        assert_eq!(err.to_string(), "<script>: marker \"nope\" does not exist");
+    }
+
+    #[test]
+    fn missing_marker() {
+        let instructions = parser::parse("clear\n\n  goto nope // here").unwrap();
+        let instructions = compile(instructions).unwrap();
+        let err = run_headless(instructions, Options::default()).unwrap_err();
+        let expected =
+            "<script>:3:3\n2: \n3:   goto nope // here\n     ^^^^^^^^^ marker \"nope\" does not exist\n";
+        assert_eq!(err.to_string(), expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_errors() {
+        let path = std::env::temp_dir().join(format!("parrot-json-errors-{}.json", std::process::id()));
+        let run = |json: &str| {
+            std::fs::write(&path, json).unwrap();
+            let instructions = serde_json::from_str(json).unwrap();
+            let instructions = crate::compile_script(instructions, &path).unwrap();
+            run_headless(instructions, Options::default()).unwrap_err().to_string()
+        };
+
+        // Without a source there is nothing to point at
+        let err = run(r#"{"instructions": [{"instruction": {"Goto": {"Marker": "nope"}}}]}"#);
+        assert_eq!(err, format!("{}: marker \"nope\" does not exist", path.display()));
+
+        // The lines of a dumped script aren't lines of the JSON file
+        let json = serde_json::to_string(&parser::parse("clear\ngoto nope").unwrap()).unwrap();
+        let err = run(&json);
+        let expected = format!(
+            "{}\n1: clear\n2: goto nope\n   ^^^^^^^^^ marker \"nope\" does not exist\n",
+            path.display()
+        );
+        assert_eq!(err, expected);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn waits_count_towards_duration() {
+        let output = play("jitter 0\nwait 3");
+        assert_eq!(output.duration, Duration::from_millis(3070));
+    }
+
+    #[test]
+    fn jitter_below_a_millisecond() {
+        let output = play("jitter 0.5ms\ntype \"hello\"");
+        let steady = play("jitter 0\ntype \"hello\"");
+        assert!(output.duration > steady.duration);
+        assert!(output.duration < steady.duration + Duration::from_millis(3));
+    }
+
+    #[test]
+    fn seeded_jitter_is_reproducible() {
+        let src = "seed 7\njitter 50\ntype \"hello world\"";
+        let a = play(src);
+        let b = play(src);
+        assert_eq!(a.duration, b.duration);
+
+        let instructions = compile(parser::parse(src).unwrap()).unwrap();
+        let options = Options {
+            seed: Some(8),
+            ..Default::default()
+        };
+        let c = run_headless(instructions, options).unwrap();
+        assert_ne!(a.duration, c.duration);
+    }
+
+    fn play_from(src: &str, from: &str) -> Result<Output> {
+        let instructions = compile(parser::parse(src).unwrap()).unwrap();
+        let options = Options {
+            from: Some(from.into()),
+            ..Default::default()
+        };
+        run_headless(instructions, options)
+    }
+
+    #[test]
+    fn seek_to_chapter() {
+        let src = "type \"one \"\nwait 10\nchapter two\ntype \"two\"";
+        let output = play_from(src, "two").unwrap();
+        assert_eq!(output.text, "one two");
+        assert!(output.duration < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn seek_to_marker() {
+        let src = "type \"// @here\na\n\"\nwait 10\ntype \"b\"";
+        let output = play_from(src, "here").unwrap();
+        assert_eq!(output.text, "a\nb");
+        assert!(output.duration > Duration::from_secs(10));
+    }
+
+    #[test]
+    fn seek_to_missing_chapter() {
+        let err = play_from("chapter one", "two").unwrap_err();
+        assert_eq!(err.to_string(), "there is no chapter or marker named \"two\"");
+    }
+}
@@ -2,14 +2,20 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use anathema::geometry::{Pos, Size};
+use parser::{Anchor, OnFinish};
 
+use crate::location::Location;
 use crate::markers::Markers;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Instruction {
     // Relative jump
     Jump(Pos),
-    JumpToMarker(String),
+    // Jump to a position in the document
+    JumpTo(Pos),
+    // Jump to a marker, then by the offset
+    JumpToMarker { name: String, offset: Pos },
+    JumpToAnchor(Anchor),
     Select(Size),
 
     // -----------------------------------------------------------------------------
@@ -32,12 +38,34 @@ pub enum Instruction {
 
     SetTitle(String),
     SetExtension(String),
-    SetJitter(u64),
+    SetJitter(Duration),
+    Seed(u64),
     SetTheme(String),
     ShowLineNumbers(bool),
-    AddMarkers { row: usize, markers: Markers },
+    AddMarkers { row: usize, col: usize, markers: Markers },
     LoadAudio(PathBuf),
     Popup(String),
     ClosePopup,
     Clear,
+    // Stop until a key is pressed, showing the hint in the status bar
+    WaitForKey(Option<String>),
+    // Does nothing during playback, but can be seeked to
+    Chapter(String),
+    OnFinish(OnFinish),
+}
+
+/// A compiled instruction, and where in the script it came from
+#[derive(Debug, Clone)]
+pub struct Located {
+    pub instruction: Instruction,
+    pub location: Option<Location>,
+}
+
+impl From<Instruction> for Located {
+    fn from(instruction: Instruction) -> Self {
+        Self {
+            instruction,
+            location: None,
+        }
+    }
 }
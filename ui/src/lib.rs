@@ -1,28 +1,44 @@
 use std::time::Duration;
 
 use anathema::prelude::*;
-pub use compile::compile;
-
-use crate::editor::Editor;
+pub use asciicast::record;
+pub use check::check_script;
+pub use compile::{compile, compile_script};
+pub use error::Error;
+pub use headless::{Output, run_headless};
+pub use location::Location;
+pub use markers::{Marker, generate as generate_markers};
+pub use options::Options;
+pub use parser::OnFinish;
+pub use svg::record_svg;
+
+use crate::editor::EditorView;
 use crate::error::Result;
-use crate::instructions::Instruction;
+use crate::instructions::Located;
 use crate::syntax::Highlighter;
 
+mod asciicast;
 mod audio;
+mod check;
 mod compile;
 mod context;
 mod document;
 mod editor;
 mod error;
+mod headless;
 mod instructions;
+mod location;
 mod markers;
+mod options;
 mod random;
+mod screen;
+mod svg;
 pub(crate) mod syntax;
 mod textbuffer;
 
 pub mod setup_paths {
     use std::io::Write;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     use crate::error::{Error, Result};
 
@@ -44,16 +60,22 @@ pub mod setup_paths {
         parrot_root().join("syntax")
     }
 
+    static TEMPLATES: [(&str, &[u8]); 4] = [
+        ("index.aml", INDEX),
+        ("status.aml", STATUS),
+        ("error.aml", ERROR),
+        ("popup.aml", POPUP),
+    ];
+
+    // Records which templates were written last
+    static STAMP: &str = ".stamp";
+
     // Ensure that templates and syntax files exists
     pub fn ensure_exists() -> Result<PathBuf> {
         let template_dir = parrot_root().join("templates");
         let syntax_dir = syntax_root();
         let theme_dir = theme_root();
 
-        if template_dir.is_dir() {
-            return Ok(template_dir);
-        }
-
         if template_dir.is_file() {
             panic!("you have in all your wisdom made a file where a template directory should be");
         }
@@ -62,17 +84,92 @@ pub mod setup_paths {
         _ = std::fs::create_dir_all(&syntax_dir);
         _ = std::fs::create_dir_all(&theme_dir);
 
-        for (path, content) in [("index.aml", INDEX), ("status.aml", STATUS), ("error.aml", ERROR), ("popup.aml", POPUP)] {
+        write_templates(&template_dir)?;
+
+        let path = theme_dir.join("togglebit.tmTheme");
+        if !path.is_file() {
+            let mut file = std::fs::File::create(&path).map_err(|_| Error::FilePath(path))?;
+            file.write_all(THEME).expect("did you run out of disk space?");
+        }
+
+        Ok(template_dir)
+    }
+
+    // The bundled templates are written again whenever they differ from the ones written last,
+    // so a new version of parrot brings its templates along to an existing config directory
+    fn write_templates(template_dir: &Path) -> Result<()> {
+        let stamp = stamp();
+        let stamp_path = template_dir.join(STAMP);
+        if std::fs::read_to_string(&stamp_path).is_ok_and(|written| written == stamp) {
+            return Ok(());
+        }
+
+        for (path, content) in TEMPLATES {
             let path = template_dir.join(path);
             let mut file = std::fs::File::create(&path).map_err(|_| Error::FilePath(path))?;
             file.write_all(content).expect("did you run out of disk space?");
         }
 
-        let path = theme_root().join("togglebit.tmTheme");
-        let mut file = std::fs::File::create(&path).map_err(|_| Error::FilePath(path))?;
-        file.write_all(THEME).expect("did you run out of disk space?");
+        std::fs::write(&stamp_path, stamp).map_err(|_| Error::FilePath(stamp_path))?;
+        Ok(())
+    }
+
+    // A hash (FNV-1a) of every bundled template, the same on every build of the same templates
+    fn stamp() -> String {
+        let mut hash = 0xcbf29ce484222325_u64;
+        for byte in TEMPLATES
+            .iter()
+            .flat_map(|(path, content)| path.as_bytes().iter().chain(*content))
+        {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{hash:016x}")
+    }
 
-        Ok(parrot_root())
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn refresh_templates() {
+            let dir = std::env::temp_dir().join(format!("parrot-templates-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            // Templates from an older version
+            std::fs::write(dir.join("status.aml"), "text attributes.title").unwrap();
+            std::fs::write(dir.join(STAMP), "old").unwrap();
+            write_templates(&dir).unwrap();
+            assert_eq!(std::fs::read(dir.join("status.aml")).unwrap(), STATUS);
+
+            // Up to date templates are left alone
+            std::fs::write(dir.join("status.aml"), "text attributes.title").unwrap();
+            write_templates(&dir).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(dir.join("status.aml")).unwrap(),
+                "text attributes.title"
+            );
+
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn refresh_pause_hint() {
+            let dir = std::env::temp_dir().join(format!("parrot-hint-templates-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            // An install from before the pause hint, without a stamp
+            std::fs::write(dir.join("index.aml"), "@status [row: state.cursor_y]").unwrap();
+            std::fs::write(dir.join("status.aml"), "text attributes.row").unwrap();
+            write_templates(&dir).unwrap();
+
+            let index = std::fs::read_to_string(dir.join("index.aml")).unwrap();
+            let status = std::fs::read_to_string(dir.join("status.aml")).unwrap();
+            assert!(index.contains("hint: state.hint"));
+            assert!(status.contains("if attributes.hint"));
+
+            std::fs::remove_dir_all(dir).unwrap();
+        }
     }
 }
 
@@ -86,9 +183,9 @@ pub fn print_themes() {
     highlighter.print_themes();
 }
 
-pub fn run(instructions: Vec<Instruction>) -> Result<()> {
+pub fn run(instructions: Vec<Located>, options: Options) -> Result<()> {
     let highlighter = Highlighter::new();
-    let editor = Editor::new(instructions, highlighter, Duration::from_millis(70));
+    let editor = EditorView::new(instructions, highlighter, Duration::from_millis(70), options);
 
     let doc = Document::new("@index");
 
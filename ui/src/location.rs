@@ -0,0 +1,67 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parser::Span;
+
+/// A script that instructions are compiled from
+#[derive(Debug)]
+pub(crate) struct Script {
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) source: String,
+}
+
+impl Script {
+    // Instructions read from JSON rather than parsed from the file
+    fn is_json(&self) -> bool {
+        self.path
+            .as_ref()
+            .is_some_and(|path| path.extension().is_some_and(|ext| ext == "json"))
+    }
+}
+
+/// Where in a script an instruction came from
+#[derive(Debug, Clone)]
+pub struct Location {
+    script: Arc<Script>,
+    start: Span,
+    end: Span,
+}
+
+impl Location {
+    pub(crate) fn new(script: Arc<Script>, start: Span, end: Span) -> Self {
+        Self { script, start, end }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.script.path.as_deref().unwrap_or(Path::new("<script>"))
+    }
+
+    pub fn line(&self) -> u16 {
+        self.start.line
+    }
+
+    pub fn col(&self) -> u16 {
+        self.start.col
+    }
+
+    // Write the file, line and column followed by the source of the instruction,
+    // underlined and labelled with the message.
+    // Instructions that weren't parsed from the script, like the ones built in code
+    // or read from a JSON file without a source, only get the file and the message.
+    pub(crate) fn fmt_message(&self, f: &mut Formatter<'_>, message: &dyn Display) -> std::fmt::Result {
+        let in_source = self.start.line as usize <= self.script.source.lines().count();
+        match (in_source, self.script.is_json()) {
+            (false, _) => write!(f, "{}: {message}", self.path().display()),
+            // The lines and columns are those of the script the JSON was dumped from
+            (true, true) => {
+                write!(f, "{}", self.path().display())?;
+                parser::snippet(f, &self.script.source, self.start, self.end, message)
+            }
+            (true, false) => {
+                write!(f, "{}:{}:{}", self.path().display(), self.line(), self.col())?;
+                parser::snippet(f, &self.script.source, self.start, self.end, message)
+            }
+        }
+    }
+}
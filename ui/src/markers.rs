@@ -1,4 +1,5 @@
 use anathema::geometry::Pos;
+use unicode_width::UnicodeWidthStr;
 
 static SYMBOLS: &[&str] = &["//", "#", ";;", ";", "--"];
 
@@ -8,17 +9,38 @@ const MIN_POSSIBLE_LEN: usize = 3;
 
 pub fn generate(text: impl Into<String>) -> (String, Option<Markers>) {
     let mut markers = vec![];
+    // Lines that only hold a marker are removed
+    let mut removed = 0;
 
     let content = text
         .into()
         .split_inclusive('\n')
         .enumerate()
-        .filter_map(|(offset, line)| match marker(offset - markers.len(), line) {
-            Some(marker) => {
+        .filter_map(|(offset, line)| {
+            let row = offset - removed;
+            if let Some(marker) = marker(row, line) {
                 markers.push(marker);
-                None
+                removed += 1;
+                return None;
+            }
+
+            match inline_marker(line) {
+                Some(inline) if inline.escaped => {
+                    let mut line = line.to_string();
+                    line.remove(inline.at);
+                    Some(line)
+                }
+                Some(inline) => {
+                    markers.push(Marker {
+                        row,
+                        col: inline.code.width(),
+                        name: inline.name.into(),
+                    });
+                    let newline = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                    Some(format!("{}{newline}", inline.code))
+                }
+                None => Some(escape_marker(line)),
             }
-            None => Some(escape_marker(line)),
         })
         .collect();
 
@@ -29,15 +51,23 @@ pub fn generate(text: impl Into<String>) -> (String, Option<Markers>) {
 // -----------------------------------------------------------------------------
 //   - Marker -
 // -----------------------------------------------------------------------------
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Marker {
     pub row: usize,
+    /// Zero for a marker on a line of its own
+    pub col: usize,
     name: String,
 }
 
+impl Marker {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl From<&Marker> for Pos {
     fn from(marker: &Marker) -> Self {
-        Self::new(0, marker.row as i32)
+        Self::new(marker.col as i32, marker.row as i32)
     }
 }
 
@@ -82,10 +112,53 @@ fn marker(offset: usize, line: &str) -> Option<Marker> {
 
     Some(Marker {
         row: offset,
+        col: 0,
         name: marker.to_string(),
     })
 }
 
+// A marker comment at the end of a line of code
+struct Inline<'a> {
+    // The code before the comment, without the whitespace after it
+    code: &'a str,
+    name: &'a str,
+    // Byte offset of the '@' that starts the marker
+    at: usize,
+    // `@@name` is kept as `@name` rather than becoming a marker
+    escaped: bool,
+}
+
+// marker: <code> <comment> @<ident>
+//
+// The comment has to be separated from the code by whitespace
+// and nothing but whitespace can come after the name of the marker.
+fn inline_marker(line: &str) -> Option<Inline<'_>> {
+    let line = line.trim_end();
+    let name = line.trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    let name = &line[name.len()..];
+    let before = line[..line.len() - name.len()].strip_suffix('@')?;
+    if name.is_empty() {
+        return None;
+    }
+
+    let escaped = before.ends_with('@');
+    let at = before.len() - usize::from(escaped);
+    let before = before[..at].trim_end();
+
+    let symbol = SYMBOLS.iter().find(|symbol| before.ends_with(*symbol))?;
+    let code = &before[..before.len() - symbol.len()];
+    if !code.ends_with(char::is_whitespace) || code.trim().is_empty() {
+        return None;
+    }
+
+    Some(Inline {
+        code: code.trim_end(),
+        name,
+        at,
+        escaped,
+    })
+}
+
 fn escape_marker(input: &str) -> String {
     let line = input.trim_start();
 
@@ -122,7 +195,7 @@ fn escape_marker(input: &str) -> String {
 // -----------------------------------------------------------------------------
 //   - Markers -
 // -----------------------------------------------------------------------------
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Markers {
     inner: Vec<Marker>,
 }
@@ -141,9 +214,34 @@ impl Markers {
         Self { inner: new }
     }
 
-    pub fn offset_after(&mut self, row: usize, offset: usize) {
-        let index = self.inner.partition_point(|marker| marker.row < row);
-        self.inner[index..].iter_mut().for_each(|marker| marker.row += offset);
+    // Make room for text inserted at `row` and `col`.
+    // Markers on the rows after it move down by its newlines, and inline markers
+    // after `col` on the same row move along with the code they follow.
+    pub fn insert(&mut self, row: usize, col: usize, text: &str) {
+        let newlines = text.matches('\n').count();
+        let last_line = text.rsplit('\n').next().unwrap_or_default().width();
+
+        for marker in self.inner.iter_mut() {
+            if marker.row == row && marker.col > 0 {
+                if marker.col > col {
+                    marker.row += newlines;
+                    marker.col = match newlines {
+                        0 => marker.col + last_line,
+                        _ => marker.col - col + last_line,
+                    };
+                }
+            } else if marker.row >= row {
+                marker.row += newlines;
+            }
+        }
+    }
+
+    // Inline markers after `col` on the row move back by the width of the deleted text
+    pub fn delete(&mut self, row: usize, col: usize, width: usize) {
+        self.inner
+            .iter_mut()
+            .filter(|marker| marker.row == row && marker.col > col)
+            .for_each(|marker| marker.col = marker.col.saturating_sub(width).max(col));
     }
 
     pub fn get(&self, key: &str) -> Option<&Marker> {
@@ -152,7 +250,14 @@ impl Markers {
 
     // * offset new markers by insertion point
     // * offset current markers *after* the insertion point with N lines
-    pub fn merge(&mut self, insert_after_row: usize, mut other: Self) {
+    pub fn merge(&mut self, insert_after_row: usize, col: usize, mut other: Self) {
+        // Markers on the first row start at the column the text is inserted at
+        other
+            .inner
+            .iter_mut()
+            .filter(|marker| marker.row == 0 && marker.col > 0)
+            .for_each(|marker| marker.col += col);
+
         // Offset the new rows by the insertion point (row)
         other.offset_by(insert_after_row);
 
@@ -209,26 +314,52 @@ c"
         }
     }
 
+    #[test]
+    fn generate_inline_markers() {
+        let s = "// @top
+fn main() {
+    let x = 1; // @here
+    x # @@kept
+}";
+
+        let (content, markers) = generate(s);
+        assert_eq!(content, "fn main() {\n    let x = 1;\n    x # @kept\n}");
+
+        let markers = markers.unwrap().inner;
+        assert_eq!((markers[0].name(), markers[0].row, markers[0].col), ("top", 0, 0));
+        assert_eq!((markers[1].name(), markers[1].row, markers[1].col), ("here", 1, 14));
+    }
+
+    #[test]
+    fn not_inline_markers() {
+        for line in ["a//@b", "a // @b c", "email @user", "a // @", "    // @own-line"] {
+            assert!(inline_marker(line).is_none(), "{line}");
+        }
+    }
+
     #[test]
     fn merge_markers() {
         let mut markers = Markers::new();
         markers.inner.push(Marker {
             row: 0,
+            col: 0,
             name: "B".to_string(),
         });
         markers.inner.push(Marker {
             row: 1,
+            col: 0,
             name: "C".to_string(),
         });
 
         let mut other = Markers::new();
         other.inner.push(Marker {
             row: 0,
+            col: 0,
             name: "A".to_string(),
         });
 
         // Insert A before B
-        markers.merge(0, other);
+        markers.merge(0, 0, other);
 
         assert_eq!(markers.inner[0].row, 0);
         assert_eq!(markers.inner[1].row, 0);
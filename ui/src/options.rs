@@ -0,0 +1,12 @@
+use parser::OnFinish;
+
+/// Settings given on the command line that take precedence over the script
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Seed for the jitter, overriding any `seed` instruction in the script
+    pub seed: Option<u64>,
+    /// Fast forward to the chapter, or marker, with this name before the playback starts
+    pub from: Option<String>,
+    /// What to do once the script is done, overriding any `onfinish` instruction in the script
+    pub on_finish: Option<OnFinish>,
+}
@@ -6,17 +6,34 @@ pub struct Random {
 
 impl Random {
     pub fn new() -> Self {
-        let state = SystemTime::now()
+        let seed = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("UNIX_EPOCH is always in the past")
             .as_millis() as u64;
-        Self { state }
+        Self::seeded(seed)
+    }
+
+    /// Create a generator that always produces the same sequence for a given seed
+    pub fn seeded(seed: u64) -> Self {
+        // Scramble the seed (splitmix64) as xorshift needs a non-zero state
+        // and similar seeds should not produce similar sequences
+        let mut state = seed.wrapping_add(0x9e3779b97f4a7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d049bb133111eb);
+        state ^= state >> 31;
+
+        Self { state: state.max(1) }
     }
 
     pub fn next(&mut self, max: u64) -> u64 {
         self.state ^= self.state >> 13;
         self.state ^= self.state << 5;
         self.state ^= self.state >> 17;
+
+        if max == 0 {
+            return 0;
+        }
+
         self.state % max
     }
 }
@@ -0,0 +1,298 @@
+use std::time::Duration;
+
+use anathema::geometry::{Pos, Size};
+use syntect::highlighting::FontStyle;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::editor::{Editor, OptVisualRange, RenderAction};
+use crate::error::Result;
+use crate::instructions::Located;
+use crate::options::Options;
+use crate::syntax::{Highlighter, Lines};
+
+// Width of a cell that is covered by the wide character to the left of it
+const CONTINUATION: char = '\0';
+
+// -----------------------------------------------------------------------------
+//   - Style -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Color {
+    Rgb(u8, u8, u8),
+    Black,
+    Red,
+    Green,
+    Grey,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Style {
+    fn new(fg: Color, bg: Color) -> Self {
+        Self {
+            fg: Some(fg),
+            bg: Some(bg),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&crate::syntax::Span<'_>> for Style {
+    fn from(span: &crate::syntax::Span<'_>) -> Self {
+        let fg = span.style.foreground;
+        Self {
+            fg: Some(Color::Rgb(fg.r, fg.g, fg.b)),
+            bg: None,
+            bold: span.style.font_style.contains(FontStyle::BOLD),
+            italic: span.style.font_style.contains(FontStyle::ITALIC),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//   - Screen -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Cell {
+    pub c: char,
+    pub style: Style,
+}
+
+impl Cell {
+    const EMPTY: Self = Self {
+        c: ' ',
+        style: Style {
+            fg: None,
+            bg: None,
+            bold: false,
+            italic: false,
+        },
+    };
+
+    /// True if this cell is covered by a wide character in the previous cell
+    pub fn is_continuation(&self) -> bool {
+        self.c == CONTINUATION
+    }
+}
+
+/// An off-screen copy of what the terminal would show for a single frame
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Screen {
+    size: Size,
+    cells: Vec<Cell>,
+}
+
+impl Screen {
+    fn new(size: Size) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::EMPTY; size.width as usize * size.height as usize],
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+        self.cells.chunks(self.size.width as usize)
+    }
+
+    fn cell_mut(&mut self, pos: Pos) -> Option<&mut Cell> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.size.width as i32 || pos.y >= self.size.height as i32 {
+            return None;
+        }
+        let index = pos.y as usize * self.size.width as usize + pos.x as usize;
+        self.cells.get_mut(index)
+    }
+
+    fn put(&mut self, c: char, style: Style, pos: Pos) {
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+
+        // Wide characters that don't fit are not drawn at all
+        if width == 2 && pos.x + 1 >= self.size.width as i32 {
+            return;
+        }
+
+        let Some(cell) = self.cell_mut(pos) else { return };
+        *cell = Cell { c, style };
+
+        if width == 2 {
+            let Some(cell) = self.cell_mut(pos + Pos::new(1, 0)) else { return };
+            *cell = Cell { c: CONTINUATION, style };
+        }
+    }
+
+    // Put a string on a single line, returning the position after the last character
+    fn put_str(&mut self, s: &str, style: Style, mut pos: Pos) -> Pos {
+        for c in s.chars() {
+            self.put(c, style, pos);
+            pos.x += c.width().unwrap_or(0) as i32;
+        }
+        pos
+    }
+
+    fn fill(&mut self, from: Pos, size: Size, style: Style) {
+        for y in from.y..from.y + size.height as i32 {
+            for x in from.x..from.x + size.width as i32 {
+                self.put(' ', style, Pos::new(x, y));
+            }
+        }
+    }
+
+    fn set_colors(&mut self, pos: Pos, fg: Color, bg: Color) {
+        let Some(cell) = self.cell_mut(pos) else { return };
+        cell.style.fg = Some(fg);
+        cell.style.bg = Some(bg);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//   - Renderer -
+// -----------------------------------------------------------------------------
+/// Draws the editor the same way the templates lay it out:
+/// the line numbers, the canvas, the cursor, the popup and the status bar.
+pub(crate) struct Renderer {
+    highlighter: Highlighter,
+    size: Size,
+}
+
+impl Renderer {
+    pub fn new(highlighter: Highlighter, size: Size) -> Self {
+        Self { highlighter, size }
+    }
+
+    fn gutter_width(&self, editor: &Editor) -> u16 {
+        if !editor.show_line_numbers {
+            return 0;
+        }
+
+        let from = -editor.offset.y + 1;
+        let to = self.size.height as i32 - 1 + from;
+        to.to_string().len() as u16 + 1
+    }
+
+    fn canvas_size(&self, editor: &Editor) -> Size {
+        let width = self.size.width.saturating_sub(self.gutter_width(editor));
+        let height = self.size.height.saturating_sub(1);
+        Size::new(width, height)
+    }
+
+    pub fn render(&self, editor: &mut Editor) -> Result<Screen> {
+        editor.scroll(self.canvas_size(editor));
+
+        let mut screen = Screen::new(self.size);
+        let gutter = self.gutter_width(editor);
+        let canvas = self.canvas_size(editor);
+
+        // Line numbers
+        if gutter > 0 {
+            let digits = gutter as usize - 1;
+            for y in 0..canvas.height as i32 {
+                let line_no = format!("{:<digits$}│", y - editor.offset.y + 1);
+                screen.put_str(&line_no, Style::default(), Pos::new(0, y));
+            }
+        }
+
+        // Canvas
+        let origin = Pos::new(gutter as i32, 0);
+        let mut lines = Lines::new();
+        self.highlighter
+            .highlight(&editor.theme, editor.doc.text(), &editor.extension, &mut lines)?;
+
+        let skip = if editor.offset.y < 0 { editor.offset.y.unsigned_abs() as usize } else { 0 };
+        for (y, spans) in lines.iter().skip(skip).take(canvas.height as usize).enumerate() {
+            let mut x = editor.offset.x;
+            for span in spans {
+                for c in span.src.chars() {
+                    let width = c.width().unwrap_or(0) as i32;
+                    if x >= 0 && x + width <= canvas.width as i32 {
+                        let pos = Pos::new(x, y as i32);
+                        let mut style = Style::from(span);
+                        if editor.selected_range.contains(pos) {
+                            style.bg = Some(Color::Red);
+                        }
+                        screen.put(c, style, origin + pos);
+                    }
+                    x += width;
+                }
+            }
+        }
+
+        // Cursor
+        let cursor = editor.cursor + editor.offset;
+        if cursor.x < canvas.width as i32 && cursor.y < canvas.height as i32 {
+            screen.set_colors(origin + cursor, Color::Black, Color::Green);
+        }
+
+        // Popup
+        if !editor.popup.is_empty() {
+            let style = Style::new(Color::Black, Color::Red);
+            let width = editor.popup.lines().map(|l| l.width()).max().unwrap_or(0) as u16 + 2;
+            let height = editor.popup.lines().count() as u16 + 2;
+            let pos = origin + cursor;
+            let right = pos.x + width as i32 - 1;
+            let bottom = pos.y + height as i32 - 1;
+
+            screen.fill(pos, Size::new(width, height), style);
+            for x in pos.x + 1..right {
+                screen.put('─', style, Pos::new(x, pos.y));
+                screen.put('─', style, Pos::new(x, bottom));
+            }
+            for y in pos.y + 1..bottom {
+                screen.put('│', style, Pos::new(pos.x, y));
+                screen.put('│', style, Pos::new(right, y));
+            }
+            screen.put('┌', style, pos);
+            screen.put('┐', style, Pos::new(right, pos.y));
+            screen.put('└', style, Pos::new(pos.x, bottom));
+            screen.put('┘', style, Pos::new(right, bottom));
+
+            for (i, line) in editor.popup.lines().enumerate() {
+                screen.put_str(line, style, pos + Pos::new(1, 1 + i as i32));
+            }
+        }
+
+        // Status bar
+        let style = Style::new(Color::Black, Color::Grey);
+        let y = self.size.height as i32 - 1;
+        screen.fill(Pos::new(0, y), Size::new(self.size.width, 1), style);
+        screen.put_str(&editor.title, style, Pos::new(0, y));
+        let position = format!("row: {} col: {} ", editor.cursor.y + 1, editor.cursor.x + 1);
+        let x = self.size.width as i32 - position.width() as i32;
+        screen.put_str(&position, style, Pos::new(x, y));
+
+        Ok(screen)
+    }
+}
+
+/// Play the instructions back without a terminal, calling `f` with the
+/// timestamp and content of every frame that differs from the one before it.
+pub(crate) fn render_frames(
+    instructions: Vec<Located>,
+    options: Options,
+    renderer: &Renderer,
+    mut f: impl FnMut(Duration, &Screen) -> Result<()>,
+) -> Result<()> {
+    let mut editor = Editor::headless(instructions, Duration::from_millis(70), options);
+    let mut timestamp = Duration::ZERO;
+    let mut previous = None;
+
+    while !editor.is_done() {
+        if let RenderAction::Render = editor.advance()? {
+            let screen = renderer.render(&mut editor)?;
+            if previous.as_ref() != Some(&screen) {
+                f(timestamp, &screen)?;
+                previous = Some(screen);
+            }
+        }
+        timestamp += editor.delay();
+    }
+
+    Ok(())
+}
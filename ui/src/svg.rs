@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::time::Duration;
+
+use anathema::geometry::Size;
+
+use crate::error::Result;
+use crate::instructions::Located;
+use crate::options::Options;
+use crate::screen::{Cell, Color, Renderer, Style, render_frames};
+use crate::syntax::Highlighter;
+
+const FONT_SIZE: f64 = 14.0;
+const CELL_WIDTH: f64 = FONT_SIZE * 0.6;
+const LINE_HEIGHT: f64 = FONT_SIZE * 1.3;
+const FONT_FAMILY: &str = "ui-monospace, SFMono-Regular, Menlo, Consolas, 'DejaVu Sans Mono', monospace";
+const BACKGROUND: &str = "#1e1e1e";
+const FOREGROUND: &str = "#d4d4d4";
+
+// How long the last frame is shown before the animation starts over
+const HOLD_LAST_FRAME: Duration = Duration::from_secs(3);
+
+/// Play the instructions back without a terminal and write them to `output`
+/// as a self-contained, looping, animated SVG.
+///
+/// Every frame is stacked below the previous one and a css animation moves
+/// the frames through the view port according to the playback timeline.
+/// Rows that are identical between frames are only written once.
+pub fn record_svg(instructions: Vec<Located>, options: Options, size: Size, mut output: impl Write) -> Result<()> {
+    let renderer = Renderer::new(Highlighter::new(), size);
+
+    let mut rows = HashMap::<Vec<Cell>, usize>::new();
+    let mut defs = String::new();
+    let mut frames = vec![];
+
+    render_frames(instructions, options, &renderer, |timestamp, screen| {
+        let frame = screen
+            .rows()
+            .map(|row| {
+                let next_id = rows.len();
+                *rows.entry(row.to_vec()).or_insert_with(|| {
+                    write_row(&mut defs, next_id, row);
+                    next_id
+                })
+            })
+            .collect::<Vec<_>>();
+        frames.push((timestamp, frame));
+        Ok(())
+    })?;
+
+    let width = size.width as f64 * CELL_WIDTH;
+    let height = size.height as f64 * LINE_HEIGHT;
+    let total = frames.last().map(|(t, _)| *t).unwrap_or_default() + HOLD_LAST_FRAME;
+
+    writeln!(
+        output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.1}" height="{height:.1}" viewBox="0 0 {width:.1} {height:.1}">"#
+    )?;
+
+    // Animation
+    writeln!(output, "<style>")?;
+    writeln!(
+        output,
+        "text {{ font-family: {FONT_FAMILY}; font-size: {FONT_SIZE}px; white-space: pre; fill: {FOREGROUND} }}"
+    )?;
+    writeln!(
+        output,
+        ".frames {{ animation: play {:.3}s steps(1, end) infinite }}",
+        total.as_secs_f64()
+    )?;
+    writeln!(output, "@keyframes play {{")?;
+    for (index, (timestamp, _)) in frames.iter().enumerate() {
+        let percent = timestamp.as_secs_f64() / total.as_secs_f64() * 100.0;
+        let y = index as f64 * height;
+        writeln!(output, "  {percent:.4}% {{ transform: translateY(-{y:.1}px) }}")?;
+    }
+    if let Some(last) = frames.len().checked_sub(1) {
+        let y = last as f64 * height;
+        writeln!(output, "  100% {{ transform: translateY(-{y:.1}px) }}")?;
+    }
+    writeln!(output, "}}")?;
+    writeln!(output, "</style>")?;
+
+    // Rows
+    writeln!(output, "<defs>")?;
+    output.write_all(defs.as_bytes())?;
+    writeln!(output, "</defs>")?;
+
+    // Frames
+    writeln!(output, r#"<rect width="100%" height="100%" fill="{BACKGROUND}"/>"#)?;
+    writeln!(output, r#"<g class="frames">"#)?;
+    for (index, (_, frame)) in frames.iter().enumerate() {
+        writeln!(output, r#"<g transform="translate(0 {:.1})">"#, index as f64 * height)?;
+        for (y, row) in frame.iter().enumerate() {
+            writeln!(output, r##"<use href="#r{row}" y="{:.1}"/>"##, y as f64 * LINE_HEIGHT)?;
+        }
+        writeln!(output, "</g>")?;
+    }
+    writeln!(output, "</g>")?;
+    writeln!(output, "</svg>")?;
+
+    output.flush()?;
+    Ok(())
+}
+
+// Write a single row as a group of background rectangles and text runs
+fn write_row(defs: &mut String, id: usize, row: &[Cell]) {
+    _ = writeln!(defs, r#"<g id="r{id}">"#);
+
+    for (x, width, style) in runs(row, |cell| cell.style.bg) {
+        let Some(bg) = style.bg else { continue };
+        _ = writeln!(
+            defs,
+            r#"<rect x="{:.1}" width="{:.1}" height="{LINE_HEIGHT:.1}" fill="{}"/>"#,
+            x as f64 * CELL_WIDTH,
+            width as f64 * CELL_WIDTH,
+            hex(bg),
+        );
+    }
+
+    for (x, width, style) in runs(row, |cell| cell.style) {
+        let text = row[x..x + width]
+            .iter()
+            .filter(|cell| !cell.is_continuation())
+            .map(|cell| cell.c)
+            .collect::<String>();
+
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        _ = write!(
+            defs,
+            r#"<text x="{:.1}" y="{:.1}" textLength="{:.1}" lengthAdjust="spacingAndGlyphs""#,
+            x as f64 * CELL_WIDTH,
+            (LINE_HEIGHT + FONT_SIZE * 0.7) / 2.0,
+            width as f64 * CELL_WIDTH,
+        );
+        if let Some(fg) = style.fg {
+            _ = write!(defs, r#" fill="{}""#, hex(fg));
+        }
+        if style.bold {
+            _ = write!(defs, r#" font-weight="bold""#);
+        }
+        if style.italic {
+            _ = write!(defs, r#" font-style="italic""#);
+        }
+        _ = writeln!(defs, ">{}</text>", escape(&text));
+    }
+
+    _ = writeln!(defs, "</g>");
+}
+
+// Group neighbouring cells with the same key into runs of (start, width, style)
+fn runs<T: PartialEq>(row: &[Cell], key: impl Fn(&Cell) -> T) -> Vec<(usize, usize, Style)> {
+    let mut runs: Vec<(usize, usize, Style)> = vec![];
+
+    for (x, cell) in row.iter().enumerate() {
+        match runs.last_mut() {
+            Some((start, width, _)) if cell.is_continuation() || key(&row[*start]) == key(cell) => *width += 1,
+            _ => runs.push((x, 1, cell.style)),
+        }
+    }
+
+    runs
+}
+
+fn hex(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+        Color::Black => "#000000".into(),
+        Color::Red => "#cd3131".into(),
+        Color::Green => "#0dbc79".into(),
+        Color::Grey => "#c0c0c0".into(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_text() {
+        assert_eq!("a &lt;b&gt; &amp; &quot;c&quot;", escape("a <b> & \"c\""));
+    }
+
+    #[test]
+    fn record_script() {
+        let instructions = crate::compile(parser::parse("jitter 0\nspeed 100\ntype \"ab\"").unwrap()).unwrap();
+        let mut output = vec![];
+        record_svg(instructions, Options::default(), Size::new(10, 3), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // The frames at 0.07s and 0.17s are the same as the first one, so they are left out
+        let timeline = "@keyframes play {
+  0.0000% { transform: translateY(-0.0px) }
+  8.0119% { transform: translateY(-54.6px) }
+  10.9792% { transform: translateY(-109.2px) }
+  100% { transform: translateY(-109.2px) }
+}";
+        assert!(output.contains(".frames { animation: play 3.370s steps(1, end) infinite }"));
+        assert!(output.contains(timeline));
+        assert_eq!(output.matches(r#"<g transform="translate(0 "#).count(), 3);
+
+        // The cursor moves along with the text
+        for x in ["0.0", "8.4", "16.8"] {
+            let cursor = format!(r##"<rect x="{x}" width="8.4" height="18.2" fill="#0dbc79"/>"##);
+            assert!(output.contains(&cursor), "{cursor}");
+        }
+        assert!(output.contains(">ab</text>"));
+    }
+}
@@ -17,7 +17,7 @@ pub struct InactiveScratch {
 impl InactiveScratch {
     pub unsafe fn activate<'a>(&mut self, code: &'a str) -> ActiveScratch<'a> {
         ActiveScratch {
-            lines: unsafe { std::mem::transmute(&mut self.lines) },
+            lines: unsafe { std::mem::transmute::<&mut Lines<'static>, &mut Lines<'a>>(&mut self.lines) },
             code,
         }
     }
@@ -171,6 +171,10 @@ impl Highlighter {
         Ok(())
     }
 
+    pub(crate) fn has_theme(&self, theme_name: &str) -> bool {
+        self.theme_set.themes.contains_key(theme_name)
+    }
+
     pub(crate) fn print_syntaxes(&self) {
         for syntax in self.set.syntaxes() {
             println!("{}", syntax.name);
@@ -18,6 +18,15 @@ impl TextBuffer {
         self.inner.push_str(s.as_ref());
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.index == self.inner.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.index = 0;
+    }
+
     pub fn next(&mut self) -> Option<&str> {
         if self.index == self.inner.len() {
             self.index = 0;